@@ -10,6 +10,8 @@ pub fn screen_engine_derive(input: TokenStream) -> TokenStream {
     
     let mut has_mouse = None;
     let mut has_keyboard = None;
+    let mut has_color_map = None;
+    let mut has_palette = None;
     
     if let Data::Struct(data) = &input.data {
         for field in &data.fields {
@@ -20,6 +22,8 @@ pub fn screen_engine_derive(input: TokenStream) -> TokenStream {
                         match type_name.as_str() {
                             "MousePress" => has_mouse = Some(ident),
                             "Keyboard" => has_keyboard = Some(ident),
+                            "ColorMap" => has_color_map = Some(ident),
+                            "Palette" => has_palette = Some(ident),
                             _ => {}
                         }
                     }
@@ -30,26 +34,50 @@ pub fn screen_engine_derive(input: TokenStream) -> TokenStream {
     
     let mouse_reset = has_mouse.map(|field| {
         quote! {
-            self.#field.just_pressed = false;
+            self.#field.end_frame();
         }
     });
     
     let keyboard_reset = has_keyboard.map(|field| {
         quote! {
-            self.#field.keys_just_pressed.clear();
+            self.#field.end_frame();
         }
     });
     
+    //Engines without their own display palette just show colors as is
+    let display_palette = match has_color_map {
+        Some(field) => quote! { &self.#field },
+        None => quote! { &crate::render::colors::ALL_COLORS },
+    };
+    
+    //Same deal for the rgb values, UI engines stick to the built in colors
+    let palette = match has_palette {
+        Some(field) => quote! { &self.#field },
+        None => quote! { &crate::render::colors::DEFAULT_PALETTE },
+    };
+    
     let expanded = quote! {
         impl ScreenEngine for #name {
-            fn pixels(&self) -> &PixelsType {
+            fn pixels(&self) -> &crate::render::framebuffer::Framebuffer {
                 &self.pixels
             }
             
+            fn pixels_mut(&mut self) -> &mut crate::render::framebuffer::Framebuffer {
+                &mut self.pixels
+            }
+            
             fn reset_inputs(&mut self) {
                 #mouse_reset
                 #keyboard_reset
             }
+            
+            fn display_palette(&self) -> &crate::render::colors::ColorMap {
+                #display_palette
+            }
+            
+            fn palette(&self) -> &crate::render::colors::Palette {
+                #palette
+            }
         }
     };
     
@@ -14,9 +14,13 @@ use winit::{
 use rico_32::{
     engine::{
         game::GameEngine,
-        rico::{bind_keyboard, bind_mouse_input, bind_mouse_move, handle_engine_update},
+        rico::{
+            bind_keyboard, bind_mouse_input, bind_mouse_left, bind_mouse_move, bind_text,
+            handle_engine_update, wheel_lines,
+        },
     },
-    scripting::cartridge::{get_cart, Cartridge},
+    input::replay::InputMode,
+    scripting::cartridge::{get_cart, read_cart, Cartridge},
 };
 
 pub const SCREEN_SIZE: usize = 128;
@@ -34,8 +38,8 @@ fn main() {
         .expect("Could not create RICO-32 window!");
 
     let args: Vec<String> = env::args().collect();
-    let cart = if args.len() == 2 {
-        let cart = if let Some(value) = args[1].strip_prefix("--with-cart=") {
+    let cart = match args.iter().skip(1).find_map(|arg| arg.strip_prefix("--with-cart=")) {
+        Some(value) => {
             let compressed_bytes =
                 general_purpose::STANDARD.decode(value).expect("Could not decode");
             let mut decoder = GzDecoder::new(&compressed_bytes[..]);
@@ -45,19 +49,16 @@ fn main() {
                 bincode::decode_from_slice(&decompressed, standard())
                     .expect("Could not decode cart");
             cart
-        } else {
-            get_cart().expect("Could not load/create cartridge")
-        };
-        cart
-    } else {
-        get_cart().expect("Could not load/create cartridge")
+        }
+        None => get_cart().expect("Could not load/create cartridge"),
     };
 
-    let mut eng = GameEngine::new(cart);
+    let mut eng = GameEngine::with_input(cart, InputMode::from_args(&args));
 
     let surface_texture = SurfaceTexture::new(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32, &window);
     let mut pixels = Pixels::new(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32, surface_texture)
         .expect("Could not start pixels");
+    let mut os_cursor_hidden = false;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -68,6 +69,12 @@ fn main() {
                 eng.update();
                 handle_engine_update(buffer, &mut *eng.lua_api.borrow_mut(), 0, 0);
 
+                let hide = eng.hides_os_cursor();
+                if hide != os_cursor_hidden {
+                    window.set_cursor_visible(!hide);
+                    os_cursor_hidden = hide;
+                }
+
                 if pixels.render().is_err() {
                     *control_flow = ControlFlow::Exit;
                 }
@@ -77,12 +84,32 @@ fn main() {
                 window.request_redraw();
             }
 
+            //Recordings only get closed properly here, so quitting any way still saves them
+            Event::LoopDestroyed => {
+                if let Some(saved) = eng.stop_input_recording() {
+                    println!("{saved}");
+                }
+            }
+
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
 
+                //Just plays it, the player never writes to main.r32
+                WindowEvent::DroppedFile(path) => {
+                    match read_cart(&path).and_then(Cartridge::validated) {
+                        Ok(cart) => {
+                            let mode = eng.input.mode().for_other_cart();
+                            eng.replace(cart, mode);
+                            println!("Loaded {}", path.display());
+                        }
+                        Err(err) => println!("Could not load {}: {err}", path.display()),
+                    }
+                }
+
                 WindowEvent::KeyboardInput { input, .. } => {
                     if let Some(keycode) = input.virtual_keycode {
                         let mut lua_api = eng.lua_api.borrow_mut();
+                        lua_api.capture_hotkey(input.state, keycode);
                         bind_keyboard(&mut lua_api.keyboard, input.state, keycode);
 
                         if keycode == winit::event::VirtualKeyCode::Escape {
@@ -91,6 +118,14 @@ fn main() {
                     }
                 }
 
+                WindowEvent::ReceivedCharacter(c) => {
+                    bind_text(&mut eng.lua_api.borrow_mut().keyboard, c);
+                }
+
+                WindowEvent::MouseWheel { delta, .. } => {
+                    eng.lua_api.borrow_mut().mouse.wheel += wheel_lines(delta);
+                }
+
                 WindowEvent::MouseInput { button, state, .. } => {
                     bind_mouse_input(&mut eng.lua_api.borrow_mut().mouse, button, state);
                 }
@@ -108,6 +143,10 @@ fn main() {
                     );
                 }
 
+                WindowEvent::CursorLeft { .. } => {
+                    bind_mouse_left(&mut eng.lua_api.borrow_mut().mouse);
+                }
+
                 _ => {}
             },
             _ => {}
@@ -0,0 +1,247 @@
+use crate::{
+    engine::{
+        rico::PixelsType,
+        sprite::{CANVAS_SIZE, CANVAS_X, DRAW_Y},
+    },
+    input::mouse::MousePress,
+    render::{
+        colors::Colors,
+        framebuffer::Framebuffer,
+        pixels::{print_scr_mid, rect, rect_fill, set_pix},
+    },
+    scripting::animation::{AnimFrame, Animation, DEFAULT_FRAME_MS, MAX_FRAME_MS},
+};
+
+const CLIP_BUTTON: (i32, i32, i32, i32) = (CANVAS_X, DRAW_Y, 74, 9);
+const ADD_CLIP_BUTTON: (i32, i32, i32, i32) = (CANVAS_X + 76, DRAW_Y, 9, 9);
+const DEL_CLIP_BUTTON: (i32, i32, i32, i32) = (CANVAS_X + 87, DRAW_Y, 9, 9);
+const MODE_BUTTON: (i32, i32, i32, i32) = (CANVAS_X, DRAW_Y + 12, 46, 9);
+const PREV_FRAME: (i32, i32, i32, i32) = (CANVAS_X, DRAW_Y + 24, 9, 9);
+const NEXT_FRAME: (i32, i32, i32, i32) = (CANVAS_X + 36, DRAW_Y + 24, 9, 9);
+const SPRITE_TEXT_Y: i32 = DRAW_Y + 38;
+const DUR_MINUS: (i32, i32, i32, i32) = (CANVAS_X, DRAW_Y + 46, 9, 9);
+const DUR_PLUS: (i32, i32, i32, i32) = (CANVAS_X + 44, DRAW_Y + 46, 9, 9);
+const MOVE_LEFT: (i32, i32, i32, i32) = (CANVAS_X + 20, DRAW_Y + 58, 9, 9);
+const MOVE_RIGHT: (i32, i32, i32, i32) = (CANVAS_X + 31, DRAW_Y + 58, 9, 9);
+const DEL_FRAME: (i32, i32, i32, i32) = (CANVAS_X + 44, DRAW_Y + 58, 9, 9);
+const DUR_STEP: u32 = 10;
+const PREVIEW_SIZE: i32 = 32;
+const PREVIEW_X: i32 = CANVAS_X + CANVAS_SIZE - PREVIEW_SIZE;
+const PREVIEW_Y: i32 = DRAW_Y + 12;
+//How long a once clip sits on its last frame before the preview starts over
+const ONCE_PAUSE_MS: u64 = 500;
+const STRIP_Y: i32 = DRAW_Y + 72;
+const STRIP_CELL: i32 = 16;
+const STRIP_COLS: usize = 6;
+
+fn clicked(mouse: &MousePress, x: i32, y: i32, w: i32, h: i32) -> bool {
+    mouse.just_pressed
+        && mouse.x != -1
+        && mouse.x >= x
+        && mouse.x < x + w
+        && mouse.y >= y
+        && mouse.y < y + h
+}
+
+fn text_button(
+    pixels: &mut Framebuffer,
+    mouse: &MousePress,
+    button: (i32, i32, i32, i32),
+    text: &str,
+) -> bool {
+    let (x, y, w, h) = button;
+    rect(pixels, x, y, w, h, Colors::Gray);
+    print_scr_mid(pixels, x + 2, y + 2, Colors::Gray, text.to_string());
+    clicked(mouse, x, y, w + 1, h + 1)
+}
+
+//Squishes or blows up any sprite size into a size x size square
+fn draw_fit(pixels: &mut Framebuffer, x: i32, y: i32, size: i32, sprite: &PixelsType) {
+    let scale = sprite.len() as i32;
+    for i in 0..size {
+        for j in 0..size {
+            let col = sprite[(i * scale / size) as usize][(j * scale / size) as usize];
+            set_pix(pixels, y + i, x + j, col);
+        }
+    }
+}
+
+/* Takes over the canvas in the sprite editor while animations are being edited
+ * Sprites clicked in the sheet get added as frames after the selected one
+ */
+pub struct AnimStrip {
+    pub animations: Vec<Animation>,
+    selected: usize,
+    frame: usize,
+    preview_ms: u64,
+}
+
+impl AnimStrip {
+    pub fn new(animations: Vec<Animation>) -> Self {
+        AnimStrip { animations, selected: 0, frame: 0, preview_ms: 0 }
+    }
+
+    pub fn label(&self) -> String {
+        match self.animations.get(self.selected) {
+            Some(anim) => "Editing anim ".to_owned() + &anim.name,
+            None => "No animations".to_string(),
+        }
+    }
+
+    fn add_clip(&mut self) {
+        let mut n = self.animations.len();
+        while self.animations.iter().any(|a| a.name == format!("anim{}", n)) {
+            n += 1;
+        }
+        self.animations.push(Animation::new(format!("anim{}", n)));
+        self.select(self.animations.len() - 1);
+    }
+
+    fn select(&mut self, clip: usize) {
+        self.selected = clip;
+        self.frame = 0;
+        self.preview_ms = 0;
+    }
+
+    //Starts a clip if there isn't one yet so the first click already does something
+    pub fn add_frame(&mut self, sprite: usize) {
+        if self.animations.is_empty() {
+            self.add_clip();
+        }
+        let anim = &mut self.animations[self.selected];
+        let at = if anim.frames.is_empty() { 0 } else { self.frame + 1 };
+        anim.frames.insert(at, AnimFrame { sprite, ms: DEFAULT_FRAME_MS });
+        self.frame = at;
+    }
+
+    //Returns whether any clip changed so the sprite editor knows it's unsaved
+    pub fn update(
+        &mut self,
+        pixels: &mut Framebuffer,
+        mouse: &MousePress,
+        sheet: &[PixelsType],
+        dt: u128,
+    ) -> bool {
+        let before = self.animations.clone();
+
+        let name = self.animations.get(self.selected).map_or("-", |a| a.name.as_str()).to_owned();
+        if text_button(pixels, mouse, CLIP_BUTTON, &name) && !self.animations.is_empty() {
+            self.select((self.selected + 1) % self.animations.len());
+        }
+        if text_button(pixels, mouse, ADD_CLIP_BUTTON, "+") {
+            self.add_clip();
+        }
+        if text_button(pixels, mouse, DEL_CLIP_BUTTON, "-") && self.selected < self.animations.len()
+        {
+            self.animations.remove(self.selected);
+            self.select(self.selected.saturating_sub(1));
+        }
+
+        let Some(anim) = self.animations.get_mut(self.selected) else {
+            print_scr_mid(
+                pixels,
+                CANVAS_X,
+                DRAW_Y + 14,
+                Colors::Gray,
+                "+ for a new one".to_string(),
+            );
+            return self.animations != before;
+        };
+
+        if text_button(pixels, mouse, MODE_BUTTON, anim.mode.name()) {
+            anim.mode = anim.mode.next();
+            self.preview_ms = 0;
+        }
+
+        //Preview keeps running while editing so timing changes show up right away
+        self.preview_ms += dt as u64;
+        if anim.finished(self.preview_ms.saturating_sub(ONCE_PAUSE_MS)) {
+            self.preview_ms = 0;
+        }
+        let playing = anim.frame_at(self.preview_ms);
+        rect(
+            pixels,
+            PREVIEW_X - 1,
+            PREVIEW_Y - 1,
+            PREVIEW_SIZE + 2,
+            PREVIEW_SIZE + 2,
+            Colors::Gray,
+        );
+        if let Some(sprite) = playing.and_then(|i| sheet.get(anim.frames[i].sprite)) {
+            draw_fit(pixels, PREVIEW_X, PREVIEW_Y, PREVIEW_SIZE, sprite);
+        }
+
+        if anim.frames.is_empty() {
+            print_scr_mid(pixels, CANVAS_X, DRAW_Y + 26, Colors::Gray, "Click sprites".to_string());
+            print_scr_mid(pixels, CANVAS_X, DRAW_Y + 32, Colors::Gray, "below to add".to_string());
+            return self.animations != before;
+        }
+        self.frame = self.frame.min(anim.frames.len() - 1);
+
+        if text_button(pixels, mouse, PREV_FRAME, "<") {
+            self.frame = self.frame.saturating_sub(1);
+        }
+        if text_button(pixels, mouse, NEXT_FRAME, ">") {
+            self.frame = (self.frame + 1).min(anim.frames.len() - 1);
+        }
+        let count = format!("{}/{}", self.frame + 1, anim.frames.len());
+        print_scr_mid(pixels, CANVAS_X + 12, DRAW_Y + 26, Colors::Gray, count);
+
+        let frame = &mut anim.frames[self.frame];
+        print_scr_mid(
+            pixels,
+            CANVAS_X,
+            SPRITE_TEXT_Y,
+            Colors::Gray,
+            format!("Spr {}", frame.sprite),
+        );
+        if text_button(pixels, mouse, DUR_MINUS, "-") {
+            frame.ms = frame.ms.saturating_sub(DUR_STEP).max(DUR_STEP);
+        }
+        if text_button(pixels, mouse, DUR_PLUS, "+") {
+            frame.ms = (frame.ms + DUR_STEP).min(MAX_FRAME_MS);
+        }
+        let ms = format!("{}ms", frame.ms);
+        print_scr_mid(pixels, CANVAS_X + 12, DUR_MINUS.1 + 2, Colors::Gray, ms);
+
+        print_scr_mid(pixels, CANVAS_X, MOVE_LEFT.1 + 2, Colors::Gray, "Mv".to_string());
+        if text_button(pixels, mouse, MOVE_LEFT, "<") && self.frame > 0 {
+            anim.frames.swap(self.frame, self.frame - 1);
+            self.frame -= 1;
+        }
+        if text_button(pixels, mouse, MOVE_RIGHT, ">") && self.frame + 1 < anim.frames.len() {
+            anim.frames.swap(self.frame, self.frame + 1);
+            self.frame += 1;
+        }
+        if text_button(pixels, mouse, DEL_FRAME, "x") {
+            anim.frames.remove(self.frame);
+            self.frame = self.frame.saturating_sub(1);
+        }
+
+        //Strip pages along with the selected frame
+        let page = self.frame / STRIP_COLS * STRIP_COLS;
+        for (i, frame) in anim.frames.iter().enumerate().skip(page).take(STRIP_COLS) {
+            let x = CANVAS_X + (i - page) as i32 * STRIP_CELL;
+            if let Some(sprite) = sheet.get(frame.sprite) {
+                draw_fit(pixels, x, STRIP_Y, STRIP_CELL, sprite);
+            }
+            let col = if i == self.frame { Colors::White } else { Colors::Gray };
+            rect(pixels, x, STRIP_Y, STRIP_CELL, STRIP_CELL, col);
+            if playing == Some(i) {
+                rect_fill(
+                    pixels,
+                    x + 2,
+                    STRIP_Y + STRIP_CELL + 1,
+                    STRIP_CELL - 4,
+                    1,
+                    Colors::Yellow,
+                );
+            }
+            if clicked(mouse, x, STRIP_Y, STRIP_CELL, STRIP_CELL) {
+                self.frame = i;
+            }
+        }
+
+        self.animations != before
+    }
+}
@@ -3,10 +3,11 @@ use std::time::Instant;
 use macro_procs::ScreenEngine;
 
 use crate::{
-    engine::rico::{PixelsType, ScreenEngine, SCREEN_SIZE},
+    engine::rico::{ScreenEngine, SCREEN_SIZE},
     input::mouse::MousePress,
     render::{
         colors::Colors,
+        framebuffer::Framebuffer,
         pixels::{circle, clear, draw, print_scr_mid, rect_fill},
     },
     scripting::lua::LogTypes,
@@ -14,7 +15,7 @@ use crate::{
 
 #[derive(ScreenEngine)]
 pub struct ConsoleEngine {
-    pixels: PixelsType,
+    pixels: Framebuffer,
     pub last_time: Instant,
     pub halted: bool,
     pub mouse: MousePress,
@@ -38,7 +39,7 @@ const RESTART_IMAGE: [[Colors; 7]; 7] = [
 impl Default for ConsoleEngine {
     fn default() -> Self {
         ConsoleEngine {
-            pixels: Colors::pixels(SCREEN_SIZE, SCREEN_SIZE),
+            pixels: Framebuffer::new(SCREEN_SIZE, SCREEN_SIZE),
             last_time: Instant::now(),
             halted: false,
             mouse: MousePress::default(),
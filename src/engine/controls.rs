@@ -0,0 +1,188 @@
+use std::time::Instant;
+
+use macro_procs::ScreenEngine;
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    engine::{
+        rico::{ScreenEngine, SCREEN_SIZE},
+        sprite::{Utils, BUTTON_WIDTH},
+    },
+    input::{
+        controller::{Button, Controls, BUTTONS, PLAYERS},
+        keyboard::{key_name, Keyboard},
+        mouse::MousePress,
+    },
+    render::{
+        colors::Colors,
+        framebuffer::Framebuffer,
+        pixels::{clear, draw, image_from_util, print_scr_mid, rect, rect_fill},
+    },
+    time::sync,
+};
+
+const FRAME_RATE: i32 = 60;
+const TOP_Y: i32 = 4;
+const SAVE_X: i32 = 112;
+const DEFAULTS_BUTTON: (i32, i32, i32, i32) = (4, TOP_Y + 2, 37, 9);
+const PLAYER_Y: i32 = 24;
+const PLAYER_HEIGHT: i32 = 84;
+const ROW_HEIGHT: i32 = 10;
+const KEYS_X: i32 = 32;
+const KEYS_CHARS: usize = 22;
+const HELP_Y: i32 = PLAYER_Y + PLAYER_HEIGHT * PLAYERS as i32 + 4;
+
+/* Rebinding screen for btn and btnp, click a row and press the key for it
+ * Left click swaps the keys out, right click adds another one
+ */
+#[derive(ScreenEngine)]
+pub struct ControlsEngine {
+    pixels: Framebuffer,
+    pub mouse: MousePress,
+    pub keyboard: Keyboard,
+    pub controls: Controls,
+
+    //Row waiting for a key and whether the key gets added instead of replacing
+    waiting: Option<(usize, Button, bool)>,
+
+    last_time: Instant,
+    upto_date: bool,
+}
+
+impl ControlsEngine {
+    pub fn new(controls: Controls) -> Self {
+        ControlsEngine {
+            pixels: Framebuffer::new(SCREEN_SIZE, SCREEN_SIZE * 2),
+            mouse: MousePress::default(),
+            keyboard: Keyboard::default(),
+            controls,
+            waiting: None,
+            last_time: Instant::now(),
+            upto_date: true,
+        }
+    }
+
+    fn hovered(&self, x: i32, y: i32, w: i32, h: i32) -> bool {
+        self.mouse.x != -1
+            && self.mouse.x >= x
+            && self.mouse.x < x + w
+            && self.mouse.y >= y
+            && self.mouse.y < y + h
+    }
+
+    fn text_button(&mut self, button: (i32, i32, i32, i32), text: String) -> bool {
+        let (x, y, w, h) = button;
+        rect(&mut self.pixels, x, y, w, h, Colors::Gray);
+        print_scr_mid(&mut self.pixels, x + 2, y + 2, Colors::Gray, text);
+        self.mouse.just_pressed && self.hovered(x, y, w + 1, h + 1)
+    }
+
+    fn draw_top_bar(&mut self) {
+        if self.text_button(DEFAULTS_BUTTON, "Defaults".to_string()) {
+            self.controls = Controls::default();
+            self.waiting = None;
+            self.upto_date = false;
+        }
+        if !self.upto_date {
+            print_scr_mid(&mut self.pixels, 44, TOP_Y + 4, Colors::Gray, "*".to_string());
+        }
+
+        draw(&mut self.pixels, SAVE_X + 1, TOP_Y + 1, &image_from_util(Utils::Save));
+        if self.mouse.just_pressed && self.hovered(SAVE_X, TOP_Y, BUTTON_WIDTH, BUTTON_WIDTH) {
+            self.upto_date = self.controls.save().is_ok();
+        }
+    }
+
+    //A key belongs to one button at a time, otherwise one press would count for both
+    fn bind(&mut self, player: usize, button: Button, add: bool, key: VirtualKeyCode) {
+        for keys in self.controls.bindings.iter_mut().flatten() {
+            keys.retain(|k| *k != key);
+        }
+        let keys = self.controls.keys_mut(player, button);
+        if !add {
+            keys.clear();
+        }
+        keys.push(key);
+        self.upto_date = false;
+    }
+
+    fn capture_key(&mut self) {
+        let Some((player, button, add)) = self.waiting else {
+            return;
+        };
+        //Keys lua has no name for can't be saved, so they just keep it waiting
+        let key = self.keyboard.keys_just_pressed.iter().copied().find(|k| key_name(*k).is_some());
+        if let Some(key) = key {
+            self.bind(player, button, add, key);
+            self.waiting = None;
+        }
+    }
+
+    fn draw_player(&mut self, player: usize) {
+        let top = PLAYER_Y + player as i32 * PLAYER_HEIGHT;
+        print_scr_mid(&mut self.pixels, 4, top, Colors::White, format!("Player {}", player));
+
+        for (i, button) in BUTTONS.iter().enumerate() {
+            let y = top + 10 + i as i32 * ROW_HEIGHT;
+            let row = (2, y - 2, SCREEN_SIZE as i32 - 4, ROW_HEIGHT);
+
+            if self.hovered(row.0, row.1, row.2, row.3) {
+                if self.mouse.just_pressed {
+                    self.waiting = Some((player, *button, false));
+                } else if self.mouse.right.just_pressed {
+                    self.waiting = Some((player, *button, true));
+                }
+            }
+
+            let waiting = self.waiting.is_some_and(|(p, b, _)| p == player && b == *button);
+            if waiting {
+                rect_fill(&mut self.pixels, row.0, row.1, row.2, row.3 - 1, Colors::Gray);
+            }
+            let col = if waiting { Colors::Black } else { Colors::Gray };
+            print_scr_mid(&mut self.pixels, 4, y, col, button.to_string());
+
+            let keys = if waiting {
+                "Press a key".to_string()
+            } else {
+                let names: Vec<&str> = self
+                    .controls
+                    .keys(player, *button)
+                    .iter()
+                    .filter_map(|key| key_name(*key))
+                    .collect();
+                if names.is_empty() {
+                    "-".to_string()
+                } else {
+                    names.join(" ").chars().take(KEYS_CHARS).collect()
+                }
+            };
+            let col = if waiting { Colors::Black } else { Colors::White };
+            print_scr_mid(&mut self.pixels, KEYS_X, y, col, keys);
+        }
+    }
+
+    pub fn update(&mut self) {
+        sync(&mut self.last_time, FRAME_RATE);
+        clear(&mut self.pixels, Colors::Black);
+
+        self.capture_key();
+        //Clicking anywhere but a row gives up on waiting, rows set it again below
+        if self.mouse.just_pressed || self.mouse.right.just_pressed {
+            self.waiting = None;
+        }
+
+        self.draw_top_bar();
+        for player in 0..PLAYERS {
+            self.draw_player(player);
+        }
+
+        for (i, line) in
+            ["Click a button to set its key", "Right click to add another", "Saved for every cart"]
+                .iter()
+                .enumerate()
+        {
+            let y = HELP_Y + i as i32 * 8;
+            print_scr_mid(&mut self.pixels, 4, y, Colors::Gray, line.to_string());
+        }
+    }
+}
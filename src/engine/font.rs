@@ -0,0 +1,509 @@
+use std::time::Instant;
+
+use macro_procs::ScreenEngine;
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    engine::{
+        rico::{ScreenEngine, SCREEN_SIZE},
+        sprite::{
+            Utils, BUTTON_WIDTH, FRAME_HASH_MODULO, UNDO_REDO_CONTINUOUS_FRAME_DIVISOR,
+            UNDO_REDO_FRAME_DELAY,
+        },
+    },
+    input::{
+        keyboard::{Keyboard, REPEAT_DELAY, REPEAT_INTERVAL},
+        mouse::MousePress,
+    },
+    render::{
+        colors::Colors,
+        framebuffer::Framebuffer,
+        pixels::{clear, draw, image_from_util, print_scr_mid, rect, rect_fill, set_pix, Target},
+        screen::{DrawState, Screen},
+        text::{print_box, Font, TextStyle},
+    },
+    scripting::{
+        cartridge::update_fonts,
+        font::{BitmapFont, ASCII_GLYPHS, FIRST_GLYPH},
+    },
+    time::sync,
+};
+
+const FRAME_RATE: i32 = 60;
+const TOP_Y: i32 = 4;
+const SAVE_X: i32 = 112;
+const FONT_BUTTON: (i32, i32, i32, i32) = (4, TOP_Y, 76, 9);
+const ADD_FONT_BUTTON: (i32, i32, i32, i32) = (82, TOP_Y, 9, 9);
+const DEL_FONT_BUTTON: (i32, i32, i32, i32) = (93, TOP_Y, 9, 9);
+const SIZE_Y: i32 = 20;
+const WIDTH_MINUS: (i32, i32, i32, i32) = (20, SIZE_Y - 2, 9, 9);
+const WIDTH_PLUS: (i32, i32, i32, i32) = (31, SIZE_Y - 2, 9, 9);
+const HEIGHT_MINUS: (i32, i32, i32, i32) = (60, SIZE_Y - 2, 9, 9);
+const HEIGHT_PLUS: (i32, i32, i32, i32) = (71, SIZE_Y - 2, 9, 9);
+const EXT_BUTTON: (i32, i32, i32, i32) = (88, SIZE_Y - 2, 36, 9);
+const CANVAS_Y: i32 = 32;
+const CANVAS_SIZE: i32 = 96;
+const GLYPH_INFO_Y: i32 = 132;
+const ADV_MINUS: (i32, i32, i32, i32) = (104, GLYPH_INFO_Y - 2, 9, 9);
+const ADV_PLUS: (i32, i32, i32, i32) = (115, GLYPH_INFO_Y - 2, 9, 9);
+const PREVIEW: (i32, i32, i32, i32) = (4, 144, 120, 30);
+const PREVIEW_TEXT: &str = "The quick brown fox jumps over the lazy dog! 0123456789";
+const PICKER_Y: i32 = 176;
+const PICKER_COLS: i32 = 16;
+const PICKER_ROWS: i32 = 10;
+const PICKER_CELL: i32 = 8;
+//As much as fits in the font button next to the cursor and unsaved star
+const MAX_NAME_LEN: usize = 16;
+//Every step keeps a copy of all the fonts, so don't let it grow forever
+const MAX_UNDO: usize = 100;
+
+/* New fonts start as a copy of a built in one
+ * Editing letters is a lot faster than drawing 95 of them from nothing
+ */
+fn font_from_builtin(name: String, builtin: Font) -> BitmapFont {
+    let (w, h) = builtin.glyph_size();
+    let mut font = BitmapFont::new(name, w as usize, h as usize);
+    for idx in 0..ASCII_GLYPHS {
+        let c = char::from_u32(FIRST_GLYPH + idx as u32).unwrap_or(' ');
+        font.glyphs[idx].advance = builtin.advance(c) as usize;
+        for y in 0..h {
+            for x in 0..w {
+                font.set(idx, x, y, builtin.glyph_pix(c, x, y));
+            }
+        }
+    }
+    font
+}
+
+#[derive(ScreenEngine)]
+pub struct FontEngine {
+    pixels: Framebuffer,
+    pub mouse: MousePress,
+    pub keyboard: Keyboard,
+    pub fonts: Vec<BitmapFont>,
+
+    selected_font: usize,
+    selected_glyph: usize,
+    //What a drag paints, decided by the first pixel clicked
+    paint: Option<bool>,
+    //Name being typed for the selected font, lua picks fonts by it
+    renaming: Option<String>,
+
+    //Whole font lists from before each change, fonts are small enough that diffs aren't worth it
+    undo_stack: Vec<Vec<BitmapFont>>,
+    redo_stack: Vec<Vec<BitmapFont>>,
+    last_frame_ur: bool,
+    continuous_ur_frames: i32,
+
+    last_time: Instant,
+    upto_date: bool,
+    picker_row: i32,
+    frame_hash: i32,
+}
+
+impl FontEngine {
+    pub fn new(fonts: Vec<BitmapFont>) -> Self {
+        FontEngine {
+            pixels: Framebuffer::new(SCREEN_SIZE, SCREEN_SIZE * 2),
+            mouse: MousePress::default(),
+            keyboard: Keyboard::default(),
+            fonts,
+            selected_font: 0,
+            selected_glyph: 'A' as usize - FIRST_GLYPH as usize,
+            paint: None,
+            renaming: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_frame_ur: false,
+            continuous_ur_frames: 0,
+            last_time: Instant::now(),
+            upto_date: true,
+            picker_row: 0,
+            frame_hash: 0,
+        }
+    }
+
+    pub fn upto_date(&self) -> bool {
+        self.upto_date
+    }
+
+    //Call right before changing the fonts so undo can get back to how they were
+    fn checkpoint(&mut self) {
+        self.undo_stack.push(self.fonts.clone());
+        if self.undo_stack.len() > MAX_UNDO {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+        self.upto_date = false;
+    }
+
+    fn clicked(&self, x: i32, y: i32, w: i32, h: i32) -> bool {
+        self.mouse.just_pressed
+            && self.mouse.x != -1
+            && self.mouse.x >= x
+            && self.mouse.x < x + w
+            && self.mouse.y >= y
+            && self.mouse.y < y + h
+    }
+
+    fn text_button(&mut self, button: (i32, i32, i32, i32), text: String) -> bool {
+        let (x, y, w, h) = button;
+        rect(&mut self.pixels, x, y, w, h, Colors::Gray);
+        print_scr_mid(&mut self.pixels, x + 2, y + 2, Colors::Gray, text);
+        self.clicked(x, y, w + 1, h + 1)
+    }
+
+    //Pixel size of the glyph on the canvas and where its top left lands
+    fn canvas_layout(font: &BitmapFont) -> (i32, i32, i32) {
+        let zoom = (CANVAS_SIZE / font.width.max(font.height) as i32).max(1);
+        let x = (SCREEN_SIZE as i32 - font.width as i32 * zoom) / 2;
+        let y = CANVAS_Y + (CANVAS_SIZE - font.height as i32 * zoom) / 2;
+        (zoom, x, y)
+    }
+
+    pub fn scroll(&mut self, delta: f32) {
+        if self.frame_hash != 0 || delta == 0.0 || self.mouse.y < PICKER_Y {
+            return;
+        }
+        let Some(font) = self.fonts.get(self.selected_font) else {
+            return;
+        };
+
+        let rows = (font.glyphs.len() as i32 + PICKER_COLS - 1) / PICKER_COLS;
+        self.picker_row -= delta.signum() as i32;
+        self.picker_row = self.picker_row.min(rows - PICKER_ROWS).max(0);
+    }
+
+    //Built in names would always win in lua, and two fonts with one name can't both be picked
+    fn name_taken(&self, name: &str) -> bool {
+        name.parse::<Font>().is_ok()
+            || self.fonts.iter().enumerate().any(|(i, f)| i != self.selected_font && f.name == name)
+    }
+
+    /* Typing goes into the name, Enter keeps it and clicking anywhere else gives up
+     * Names that can't be used just don't take, the label shows red until they can
+     */
+    fn handle_rename(&mut self) {
+        let Some(name) = &mut self.renaming else {
+            return;
+        };
+        for c in self.keyboard.text.chars() {
+            if c.is_ascii_graphic() && name.len() < MAX_NAME_LEN {
+                name.push(c);
+            }
+        }
+        if self.keyboard.repeated(&[VirtualKeyCode::Back], REPEAT_DELAY, REPEAT_INTERVAL) {
+            name.pop();
+        }
+
+        if self.keyboard.just_pressed(&[VirtualKeyCode::Return, VirtualKeyCode::NumpadEnter]) {
+            let name = name.clone();
+            if name.is_empty() || self.name_taken(&name) {
+                return;
+            }
+            if self.fonts[self.selected_font].name != name {
+                self.checkpoint();
+                self.fonts[self.selected_font].name = name;
+            }
+            self.renaming = None;
+        } else if self.mouse.just_pressed || self.mouse.right.just_pressed {
+            self.renaming = None;
+        }
+    }
+
+    fn draw_font_controls(&mut self) {
+        let star = if self.upto_date { "" } else { "*" };
+        let label = match (self.fonts.get(self.selected_font), &self.renaming) {
+            (Some(_), Some(name)) => format!("{}_{}", name, star),
+            (Some(font), None) => format!("{}{}", font.name, star),
+            (None, _) => format!("No fonts{}", star),
+        };
+        if let Some(name) = &self.renaming {
+            if name.is_empty() || self.name_taken(name) {
+                let (x, y, w, h) = FONT_BUTTON;
+                rect_fill(&mut self.pixels, x + 1, y + 1, w - 1, h - 1, Colors::Maroon);
+            }
+        }
+        if self.text_button(FONT_BUTTON, label) && !self.fonts.is_empty() {
+            self.selected_font = (self.selected_font + 1) % self.fonts.len();
+            self.picker_row = 0;
+        }
+        //Right click renames, same as right click adding a key in the controls tab
+        let (x, y, w, h) = FONT_BUTTON;
+        if self.renaming.is_none()
+            && self.mouse.right.just_pressed
+            && self.mouse.x >= x
+            && self.mouse.x <= x + w
+            && self.mouse.y >= y
+            && self.mouse.y <= y + h
+        {
+            self.renaming = self.fonts.get(self.selected_font).map(|f| f.name.clone());
+        }
+
+        if self.text_button(ADD_FONT_BUTTON, "+".to_string()) {
+            let mut n = self.fonts.len();
+            while self.fonts.iter().any(|f| f.name == format!("font{}", n)) {
+                n += 1;
+            }
+            self.checkpoint();
+            self.fonts.push(font_from_builtin(format!("font{}", n), Font::Big));
+            self.selected_font = self.fonts.len() - 1;
+        }
+
+        if self.text_button(DEL_FONT_BUTTON, "-".to_string())
+            && self.selected_font < self.fonts.len()
+        {
+            self.checkpoint();
+            self.fonts.remove(self.selected_font);
+            self.selected_font = self.selected_font.saturating_sub(1);
+        }
+
+        draw(&mut self.pixels, SAVE_X + 1, TOP_Y + 1, &image_from_util(Utils::Save));
+        if self.clicked(SAVE_X, TOP_Y, BUTTON_WIDTH, BUTTON_WIDTH) {
+            self.upto_date = true;
+            let _ = update_fonts(&self.fonts);
+        }
+    }
+
+    fn draw_size_controls(&mut self) {
+        let Some(font) = self.fonts.get(self.selected_font) else {
+            return;
+        };
+        let (w, h, extended) = (font.width, font.height, font.extended());
+
+        print_scr_mid(&mut self.pixels, 4, SIZE_Y, Colors::Gray, format!("W{}", w));
+        print_scr_mid(&mut self.pixels, 44, SIZE_Y, Colors::Gray, format!("H{}", h));
+        let ext_text = if extended { "Latin1" } else { "ASCII" };
+
+        let mut size = (w, h);
+        if self.text_button(WIDTH_MINUS, "-".to_string()) {
+            size.0 = w.saturating_sub(1);
+        }
+        if self.text_button(WIDTH_PLUS, "+".to_string()) {
+            size.0 = w + 1;
+        }
+        if self.text_button(HEIGHT_MINUS, "-".to_string()) {
+            size.1 = h.saturating_sub(1);
+        }
+        if self.text_button(HEIGHT_PLUS, "+".to_string()) {
+            size.1 = h + 1;
+        }
+        let toggle_ext = self.text_button(EXT_BUTTON, ext_text.to_string());
+
+        if size != (w, h) {
+            self.checkpoint();
+            self.fonts[self.selected_font].resize(size.0, size.1);
+        }
+        if toggle_ext {
+            self.checkpoint();
+            let font = &mut self.fonts[self.selected_font];
+            font.set_extended(!extended);
+            self.selected_glyph = self.selected_glyph.min(font.glyphs.len() - 1);
+            self.picker_row = 0;
+        }
+    }
+
+    fn handle_canvas(&mut self) {
+        if !self.mouse.pressed {
+            self.paint = None;
+            return;
+        }
+        let Some(font) = self.fonts.get(self.selected_font) else {
+            return;
+        };
+
+        let (zoom, cx, cy) = Self::canvas_layout(font);
+        let x = (self.mouse.x - cx).div_euclid(zoom);
+        let y = (self.mouse.y - cy).div_euclid(zoom);
+        if self.mouse.x == -1 || x < 0 || y < 0 || x >= font.width as i32 || y >= font.height as i32
+        {
+            return;
+        }
+
+        let c = char::from_u32(FIRST_GLYPH + self.selected_glyph as u32).unwrap_or(' ');
+        let cur = font.get(c, x, y);
+        //The first pixel always flips, so a whole stroke is one undo step
+        if self.mouse.just_pressed {
+            self.paint = Some(!cur);
+            self.checkpoint();
+        }
+        if let Some(on) = self.paint {
+            if on != cur {
+                self.fonts[self.selected_font].set(self.selected_glyph, x, y, on);
+                self.upto_date = false;
+            }
+        }
+    }
+
+    fn draw_canvas(&mut self) {
+        rect_fill(&mut self.pixels, 0, CANVAS_Y, SCREEN_SIZE as i32, CANVAS_SIZE, Colors::Gray);
+        let Some(font) = self.fonts.get(self.selected_font) else {
+            print_scr_mid(
+                &mut self.pixels,
+                4,
+                CANVAS_Y + 4,
+                Colors::Black,
+                "Click + to add".into(),
+            );
+            return;
+        };
+
+        let (zoom, cx, cy) = Self::canvas_layout(font);
+        let c = char::from_u32(FIRST_GLYPH + self.selected_glyph as u32).unwrap_or(' ');
+        for y in 0..font.height as i32 {
+            for x in 0..font.width as i32 {
+                let col = if font.get(c, x, y) { Colors::White } else { Colors::Black };
+                rect_fill(&mut self.pixels, cx + x * zoom, cy + y * zoom, zoom, zoom, col);
+                if zoom > 2 {
+                    set_pix(&mut self.pixels, cy + y * zoom, cx + x * zoom, Colors::Gray);
+                }
+            }
+        }
+
+        //Where the next glyph starts, can sit past the box for wide spacing
+        let advance = font.glyph(c).map_or(0, |g| g.advance as i32);
+        let state = DrawState {
+            clip: Some((0, CANVAS_Y, SCREEN_SIZE as i32, CANVAS_SIZE)),
+            ..DrawState::default()
+        };
+        let mut canvas = Screen::new(&mut self.pixels, &state);
+        let bottom = CANVAS_Y + CANVAS_SIZE;
+        for y in (CANVAS_Y..bottom).step_by(2) {
+            canvas.put_pix(y, cx + advance * zoom, Colors::Red);
+        }
+
+        let code = FIRST_GLYPH as usize + self.selected_glyph;
+        let label = format!("Glyph {} {}", c, code);
+        print_scr_mid(&mut self.pixels, 4, GLYPH_INFO_Y, Colors::Gray, label);
+        print_scr_mid(&mut self.pixels, 68, GLYPH_INFO_Y, Colors::Gray, format!("Adv {}", advance));
+
+        let minus = self.text_button(ADV_MINUS, "-".to_string()) && advance > 0;
+        let plus = self.text_button(ADV_PLUS, "+".to_string());
+        if minus || plus {
+            self.checkpoint();
+        }
+        if let Some(glyph) = self.fonts[self.selected_font].glyphs.get_mut(self.selected_glyph) {
+            if minus {
+                glyph.advance -= 1;
+            }
+            if plus {
+                glyph.advance += 1;
+            }
+        }
+    }
+
+    fn draw_preview(&mut self) {
+        let Some(font) = self.fonts.get(self.selected_font) else {
+            return;
+        };
+
+        let style = TextStyle { font: Font::Custom(font), ..TextStyle::default() };
+        print_box(&mut self.pixels, PREVIEW, Colors::White, PREVIEW_TEXT, &style);
+    }
+
+    fn draw_picker(&mut self) {
+        let Some(font) = self.fonts.get(self.selected_font) else {
+            return;
+        };
+
+        let start = (self.picker_row * PICKER_COLS) as usize;
+        let mut picked = None;
+        for i in 0..(PICKER_COLS * PICKER_ROWS) as usize {
+            let idx = start + i;
+            if idx >= font.glyphs.len() {
+                break;
+            }
+
+            let x = (i as i32 % PICKER_COLS) * PICKER_CELL;
+            let y = PICKER_Y + (i as i32 / PICKER_COLS) * PICKER_CELL;
+            let c = char::from_u32(FIRST_GLYPH + idx as u32).unwrap_or(' ');
+            let bg = if idx == self.selected_glyph { Colors::Blue } else { Colors::Black };
+            rect_fill(&mut self.pixels, x, y, PICKER_CELL, PICKER_CELL, bg);
+
+            //Squished into the cell so big fonts still fit
+            let size = PICKER_CELL - 1;
+            let scale = font.width.max(font.height).max(size as usize) as i32;
+            for j in 0..size {
+                for k in 0..size {
+                    if font.get(c, k * scale / size, j * scale / size) {
+                        set_pix(&mut self.pixels, y + j, x + k, Colors::White);
+                    }
+                }
+            }
+
+            if self.clicked(x, y, PICKER_CELL, PICKER_CELL) {
+                picked = Some(idx);
+            }
+        }
+
+        if let Some(idx) = picked {
+            self.selected_glyph = idx;
+        }
+    }
+
+    //Ctrl+Z and Ctrl+R like the other editors, swaps the whole font list back
+    fn handle_undo_redo(&mut self) {
+        let mut t = 0;
+
+        if self.keyboard.keys_pressed.contains(&VirtualKeyCode::LControl)
+            && self.keyboard.keys_pressed.contains(&VirtualKeyCode::Z)
+        {
+            t = 1;
+        } else if self.keyboard.keys_pressed.contains(&VirtualKeyCode::LControl)
+            && self.keyboard.keys_pressed.contains(&VirtualKeyCode::R)
+        {
+            t = -1;
+        }
+
+        if t != 0 {
+            if self.last_frame_ur {
+                self.continuous_ur_frames += 1
+            } else {
+                self.continuous_ur_frames = 0
+            };
+            self.last_frame_ur = true;
+            if (self.continuous_ur_frames < UNDO_REDO_FRAME_DELAY && self.continuous_ur_frames > 0)
+                || (self.continuous_ur_frames % UNDO_REDO_CONTINUOUS_FRAME_DIVISOR != 0)
+            {
+                return;
+            }
+
+            let popped = if t == 1 { self.undo_stack.pop() } else { self.redo_stack.pop() };
+            if let Some(fonts) = popped {
+                let pushing = std::mem::replace(&mut self.fonts, fonts);
+                if t == 1 {
+                    self.redo_stack.push(pushing)
+                } else {
+                    self.undo_stack.push(pushing)
+                };
+                self.selected_font = self.selected_font.min(self.fonts.len().saturating_sub(1));
+                self.paint = None;
+                self.upto_date = false;
+            }
+        } else {
+            self.last_frame_ur = false;
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.frame_hash = (self.frame_hash + 1) % FRAME_HASH_MODULO;
+        sync(&mut self.last_time, FRAME_RATE);
+        clear(&mut self.pixels, Colors::Black);
+
+        //Typing a name takes the keys, so undo waits until it's done
+        if self.renaming.is_some() {
+            self.handle_rename();
+        } else {
+            self.handle_undo_redo();
+        }
+        self.draw_font_controls();
+        self.draw_size_controls();
+        if let Some(font) = self.fonts.get(self.selected_font) {
+            self.selected_glyph = self.selected_glyph.min(font.glyphs.len() - 1);
+        }
+        self.handle_canvas();
+        self.draw_canvas();
+        self.draw_preview();
+        self.draw_picker();
+    }
+}
@@ -3,6 +3,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::engine::console::ConsoleEngine;
 use crate::engine::script::ScriptEngine;
+use crate::input::replay::{InputMode, InputSession};
 use crate::scripting::cartridge::Cartridge;
 use crate::scripting::lua::{LogTypes, LuaAPI};
 use crate::time::sync;
@@ -14,20 +15,50 @@ pub struct GameEngine {
     pub script_engine: ScriptEngine,
     pub console_engine: ConsoleEngine,
     pub lua_api: Rc<RefCell<LuaAPI>>,
+    pub input: InputSession,
 }
 
 impl GameEngine {
     pub fn new(cart: Cartridge) -> Self {
-        let script_engine = ScriptEngine::new(cart.scripts);
-        let lua_api = Rc::from(RefCell::from(LuaAPI::new(cart.sprite_sheet)));
+        GameEngine::with_input(cart, InputMode::Live)
+    }
+
+    pub fn with_input(mut cart: Cartridge, mode: InputMode) -> Self {
+        let script_engine = ScriptEngine::new(std::mem::take(&mut cart.scripts));
+        let lua_api = Rc::from(RefCell::from(LuaAPI::new(cart)));
+
+        let mut eng = GameEngine {
+            script_engine,
+            lua_api,
+            console_engine: ConsoleEngine::default(),
+            input: InputSession::Live,
+        };
 
-        let mut eng =
-            GameEngine { script_engine, lua_api, console_engine: ConsoleEngine::default() };
+        //A replay that won't open still lets the game run, just with live input
+        match InputSession::start(mode) {
+            Ok(input) => eng.input = input,
+            Err(err) => eng.lua_api.borrow_mut().add_log(LogTypes::Err(err.to_string())),
+        }
+        match &eng.input {
+            InputSession::Recording(_) => {
+                eng.lua_api.borrow_mut().add_log(LogTypes::Ok("Recording input".to_string()))
+            }
+            InputSession::Replaying(replay) => {
+                let msg = format!("Replaying {}", replay.path.display());
+                eng.lua_api.borrow_mut().add_log(LogTypes::Ok(msg))
+            }
+            InputSession::Live => {}
+        }
 
         //Register all loaders if something errors just print to console screen
         if let Err(err) = eng.script_engine.register_api(eng.lua_api.clone()) {
             eng.add_errors(err);
         };
+        if let Some(seed) = eng.input.seed() {
+            if let Err(err) = eng.script_engine.seed_random(seed) {
+                eng.add_errors(err);
+            }
+        }
         if let Err(err) = eng.script_engine.boot() {
             eng.add_errors(err);
         };
@@ -56,17 +87,101 @@ impl GameEngine {
         }
     }
 
+    /* Records what update is about to see, or swaps it for the next replayed frame
+     * Replays use the dt they were recorded with, sync only keeps them at the same speed
+     * None once a replay has run out
+     */
+    fn step_input(&mut self, dt: u128) -> Option<u128> {
+        let mut lua_api = self.lua_api.borrow_mut();
+        let lua_api = &mut *lua_api;
+        match &mut self.input {
+            InputSession::Live => Some(dt),
+            InputSession::Recording(recorder) => {
+                let res =
+                    recorder.capture(dt, &lua_api.keyboard, &lua_api.mouse, &lua_api.controls);
+                if let Err(err) = res {
+                    self.input = InputSession::Live;
+                    lua_api.add_log(LogTypes::Err(format!("Input recording stopped: {err}")));
+                }
+                Some(dt)
+            }
+            InputSession::Replaying(replay) => {
+                let dt = replay.next_frame(
+                    &mut lua_api.keyboard,
+                    &mut lua_api.mouse,
+                    &mut lua_api.controls,
+                );
+                if dt.is_none() {
+                    let msg = format!("Replay finished after {} frames", replay.frames());
+                    self.input = InputSession::Live;
+                    lua_api.add_log(LogTypes::Ok(msg));
+                }
+                dt
+            }
+        }
+    }
+
+    //Swaps in a fresh game, recordings get saved first and gifs keep going across it
+    pub fn replace(&mut self, cart: Cartridge, mode: InputMode) {
+        let saved = self.stop_input_recording();
+        let game_eng = GameEngine::with_input(cart, mode);
+        if let Some(saved) = saved {
+            game_eng.lua_api.borrow_mut().add_log(saved);
+        }
+        let recorder = self.lua_api.borrow_mut().recorder.take();
+        game_eng.lua_api.borrow_mut().recorder = recorder;
+        *self = game_eng;
+    }
+
+    //Closes the recording file, None if nothing was recording
+    pub fn stop_input_recording(&mut self) -> Option<LogTypes> {
+        let recorder = match std::mem::replace(&mut self.input, InputSession::Live) {
+            InputSession::Recording(recorder) => recorder,
+            other => {
+                self.input = other;
+                return None;
+            }
+        };
+        Some(match recorder.finish() {
+            Ok((path, frames)) => {
+                LogTypes::Ok(format!("Saved {} ({frames} frames)", path.display()))
+            }
+            Err(err) => LogTypes::Err(format!("Could not save input recording: {err}")),
+        })
+    }
+
     pub fn update(&mut self) {
+        self.lua_api.borrow_mut().erase_cursor();
+
         //Halting is in console so make sure thats not true
         if !self.console_engine.halted {
             let dt = sync(&mut self.console_engine.last_time, self.lua_api.borrow().frame_rate);
 
-            if let Err(err) = self.script_engine.call_update(dt) {
-                self.add_errors(err);
+            //Halts on the last frame of a replay so it can be looked at, resuming goes live
+            match self.step_input(dt) {
+                Some(dt) => {
+                    if let Err(err) = self.script_engine.call_update(dt) {
+                        self.add_errors(err);
+                    }
+                    let mut lua_api = self.lua_api.borrow_mut();
+                    lua_api.draw_cursor();
+                    lua_api.record_frame(dt);
+                }
+                None => self.console_engine.halted = true,
             }
         }
+        //Still follows the mouse while halted
+        if self.console_engine.halted {
+            self.lua_api.borrow_mut().draw_cursor();
+        }
 
         //Might wanna store logs in the actual console at some point but thats kinda janky
         self.console_engine.update(&self.lua_api.borrow().logs);
     }
+
+    //Game pane draws its own cursor, so the real one goes away while it's over it
+    pub fn hides_os_cursor(&self) -> bool {
+        let lua_api = self.lua_api.borrow();
+        lua_api.cursor.hides_os() && lua_api.mouse.x != -1
+    }
 }
@@ -0,0 +1,510 @@
+use std::time::Instant;
+
+use macro_procs::ScreenEngine;
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    engine::{
+        rico::{PixelsType, ScreenEngine, SCREEN_SIZE},
+        sprite::{
+            Utils, BUTTON_WIDTH, FRAME_HASH_MODULO, UNDO_REDO_CONTINUOUS_FRAME_DIVISOR,
+            UNDO_REDO_FRAME_DELAY,
+        },
+    },
+    input::{keyboard::Keyboard, mouse::MousePress},
+    render::{
+        colors::{Colors, Palette},
+        framebuffer::Framebuffer,
+        pixels::{
+            clear, draw, image_from_map_tool, image_from_util, print_scr_mid, rect, rect_fill,
+            set_pix, Target,
+        },
+        screen::{DrawState, Screen},
+    },
+    scripting::{cartridge::update_map, tilemap::Tilemap},
+    time::sync,
+};
+
+const FRAME_RATE: i32 = 60;
+const TOOLS_Y: i32 = 4;
+const SAVE_X: i32 = 112;
+const INFO_Y: i32 = 20;
+const LAYER_BUTTON: (i32, i32, i32, i32) = (4, INFO_Y - 2, 32, 9);
+const ADD_LAYER_BUTTON: (i32, i32, i32, i32) = (38, INFO_Y - 2, 9, 9);
+const CELL_SIZE_BUTTON: (i32, i32, i32, i32) = (51, INFO_Y - 2, 32, 9);
+const VIEW_Y: i32 = 30;
+const VIEW_HEIGHT: i32 = 120;
+const PICKER_LABEL_Y: i32 = 153;
+const PICKER_Y: i32 = 161;
+const PICKER_COLS: i32 = 8;
+const PICKER_ROWS: i32 = 5;
+const TILE_PREVIEW_SIZE: i32 = 16;
+const ZOOM_LEVELS: [i32; 4] = [4, 8, 16, 32];
+const CELL_SIZES: [usize; 3] = [8, 16, 32];
+
+//layer, x, y, tile before the change
+type MapChange = (usize, i32, i32, Option<usize>);
+
+type DragType = Option<((i32, i32), (i32, i32))>;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum MapTools {
+    Pencil,
+    Rect,
+    Fill,
+    Eraser,
+}
+
+#[derive(ScreenEngine)]
+pub struct MapEngine {
+    pixels: Framebuffer,
+    pub mouse: MousePress,
+    pub keyboard: Keyboard,
+    pub palette: Palette,
+    pub sprite_sheet: Vec<PixelsType>,
+    pub map: Tilemap,
+    pub tool: MapTools,
+
+    selected_tile: usize,
+    layer: usize,
+    view_x: i32,
+    view_y: i32,
+    zoom: i32,
+    rect_drag: DragType,
+    pan_drag: DragType,
+
+    new_changes: Vec<MapChange>,
+    undo_stack: Vec<Vec<MapChange>>,
+    redo_stack: Vec<Vec<MapChange>>,
+    last_frame_ur: bool,
+    continuous_ur_frames: i32,
+
+    last_time: Instant,
+    upto_date: bool,
+    picker_row: i32,
+    frame_hash: i32,
+}
+
+//Draws the part of a sprite a map cell shows, squished into size x size
+fn draw_tile<T: Target>(
+    pixels: &mut T,
+    sprite: &PixelsType,
+    cell_size: usize,
+    x: i32,
+    y: i32,
+    size: i32,
+) {
+    for j in 0..size {
+        for i in 0..size {
+            let sy = j as usize * cell_size / size as usize;
+            let sx = i as usize * cell_size / size as usize;
+            let col = sprite.get(sy).and_then(|row| row.get(sx)).copied().unwrap_or(Colors::Blank);
+            set_pix(pixels, y + j, x + i, col);
+        }
+    }
+}
+
+impl MapEngine {
+    pub fn new(map: Tilemap, sprite_sheet: Vec<PixelsType>, palette: Palette) -> Self {
+        MapEngine {
+            pixels: Framebuffer::new(SCREEN_SIZE, SCREEN_SIZE * 2),
+            mouse: MousePress::default(),
+            keyboard: Keyboard::default(),
+            palette,
+            sprite_sheet,
+            map,
+            tool: MapTools::Pencil,
+            selected_tile: 0,
+            layer: 0,
+            view_x: 0,
+            view_y: 0,
+            zoom: ZOOM_LEVELS[1],
+            rect_drag: None,
+            pan_drag: None,
+            new_changes: Vec::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_frame_ur: false,
+            continuous_ur_frames: 0,
+            last_time: Instant::now(),
+            upto_date: true,
+            picker_row: 0,
+            frame_hash: 0,
+        }
+    }
+
+    pub fn upto_date(&self) -> bool {
+        self.upto_date
+    }
+
+    fn clicked(&self, x: i32, y: i32, w: i32, h: i32) -> bool {
+        self.mouse.just_pressed
+            && self.mouse.x != -1
+            && self.mouse.x >= x
+            && self.mouse.x < x + w
+            && self.mouse.y >= y
+            && self.mouse.y < y + h
+    }
+
+    fn set_cell(&mut self, x: i32, y: i32, tile: Option<usize>) {
+        let old = self.map.get(self.layer, x, y);
+        if old == tile || !self.map.set(self.layer, x, y, tile) {
+            return;
+        }
+        self.new_changes.push((self.layer, x, y, old));
+        self.upto_date = false;
+    }
+
+    fn hovered_cell(&self) -> Option<(i32, i32)> {
+        if self.mouse.x < 0
+            || self.mouse.x >= SCREEN_SIZE as i32
+            || self.mouse.y < VIEW_Y
+            || self.mouse.y >= VIEW_Y + VIEW_HEIGHT
+        {
+            return None;
+        }
+
+        let x = self.view_x + self.mouse.x / self.zoom;
+        let y = self.view_y + (self.mouse.y - VIEW_Y) / self.zoom;
+        if x as usize >= self.map.width || y as usize >= self.map.height {
+            return None;
+        }
+        Some((x, y))
+    }
+
+    fn clamp_view(&mut self) {
+        let cols = SCREEN_SIZE as i32 / self.zoom;
+        let rows = VIEW_HEIGHT / self.zoom;
+        self.view_x = self.view_x.min(self.map.width as i32 - cols).max(0);
+        self.view_y = self.view_y.min(self.map.height as i32 - rows).max(0);
+    }
+
+    //Wheel zooms over the map and scrolls over the tile picker
+    pub fn scroll(&mut self, delta: f32) {
+        if self.frame_hash != 0 || delta == 0.0 || self.mouse.x == -1 {
+            return;
+        }
+
+        if self.mouse.y >= VIEW_Y && self.mouse.y < VIEW_Y + VIEW_HEIGHT {
+            let cur = ZOOM_LEVELS.iter().position(|z| *z == self.zoom).unwrap_or(1);
+            let next = if delta > 0.0 {
+                (cur + 1).min(ZOOM_LEVELS.len() - 1)
+            } else {
+                cur.saturating_sub(1)
+            };
+
+            //Keep the cell under the mouse in place
+            let (mx, my) = (self.mouse.x / self.zoom, (self.mouse.y - VIEW_Y) / self.zoom);
+            let (cell_x, cell_y) = (self.view_x + mx, self.view_y + my);
+            self.zoom = ZOOM_LEVELS[next];
+            self.view_x = cell_x - self.mouse.x / self.zoom;
+            self.view_y = cell_y - (self.mouse.y - VIEW_Y) / self.zoom;
+            self.clamp_view();
+        } else if self.mouse.y >= PICKER_Y {
+            let rows = (self.sprite_sheet.len() as i32 + PICKER_COLS - 1) / PICKER_COLS;
+            self.picker_row -= delta.signum() as i32;
+            self.picker_row = self.picker_row.min(rows - PICKER_ROWS).max(0);
+        }
+    }
+
+    fn handle_panning(&mut self) -> bool {
+        //Space + drag pans, arrows pan one cell at a time
+        if self.keyboard.keys_pressed.contains(&VirtualKeyCode::Space)
+            && self.mouse.just_pressed
+            && self.mouse.x != -1
+        {
+            self.pan_drag = Some(((self.mouse.x, self.mouse.y), (self.view_x, self.view_y)));
+        }
+        if !self.mouse.pressed {
+            self.pan_drag = None;
+        }
+
+        if let Some(((mx, my), (vx, vy))) = self.pan_drag {
+            if self.mouse.x != -1 {
+                self.view_x = vx - (self.mouse.x - mx) / self.zoom;
+                self.view_y = vy - (self.mouse.y - my) / self.zoom;
+            }
+        }
+
+        for (key, (dx, dy)) in [
+            (VirtualKeyCode::Left, (-1, 0)),
+            (VirtualKeyCode::Right, (1, 0)),
+            (VirtualKeyCode::Up, (0, -1)),
+            (VirtualKeyCode::Down, (0, 1)),
+        ] {
+            if self.keyboard.keys_just_pressed.contains(&key)
+                || (self.frame_hash == 0 && self.keyboard.keys_pressed.contains(&key))
+            {
+                self.view_x += dx;
+                self.view_y += dy;
+            }
+        }
+        self.clamp_view();
+
+        self.pan_drag.is_some()
+    }
+
+    fn handle_tools(&mut self) {
+        let hovered = self.hovered_cell();
+
+        if self.tool == MapTools::Rect {
+            if let Some(cell) = hovered {
+                if self.mouse.just_pressed {
+                    self.rect_drag = Some((cell, cell));
+                } else if let Some((start, _)) = self.rect_drag {
+                    self.rect_drag = Some((start, cell));
+                }
+            }
+
+            if !self.mouse.pressed {
+                if let Some(((x1, y1), (x2, y2))) = self.rect_drag.take() {
+                    for y in y1.min(y2)..=y1.max(y2) {
+                        for x in x1.min(x2)..=x1.max(x2) {
+                            self.set_cell(x, y, Some(self.selected_tile));
+                        }
+                    }
+                }
+            }
+            return;
+        }
+        self.rect_drag = None;
+
+        let Some((x, y)) = hovered else {
+            return;
+        };
+        match self.tool {
+            MapTools::Pencil if self.mouse.pressed => self.set_cell(x, y, Some(self.selected_tile)),
+            MapTools::Eraser if self.mouse.pressed => self.set_cell(x, y, None),
+            MapTools::Fill if self.mouse.just_pressed => {
+                for (cx, cy) in self.map.region(self.layer, x, y) {
+                    self.set_cell(cx, cy, Some(self.selected_tile));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw_view(&mut self) {
+        rect_fill(&mut self.pixels, 0, VIEW_Y, SCREEN_SIZE as i32, VIEW_HEIGHT, Colors::Gray);
+
+        let zoom = self.zoom;
+        let hovered = self.hovered_cell();
+        let map_w = (self.map.width as i32 - self.view_x) * zoom;
+        let map_h = (self.map.height as i32 - self.view_y) * zoom;
+
+        //Clip so partially visible cells don't spill into the picker
+        let state = DrawState {
+            clip: Some((0, VIEW_Y, SCREEN_SIZE as i32, VIEW_HEIGHT)),
+            ..DrawState::default()
+        };
+        let mut view = Screen::new(&mut self.pixels, &state);
+        rect_fill(&mut view, 0, VIEW_Y, map_w, map_h, Colors::Black);
+
+        let cols = SCREEN_SIZE as i32 / zoom + 1;
+        let rows = VIEW_HEIGHT / zoom + 1;
+        //Layers under the current one get drawn too so you can see what you're painting over
+        for layer in 0..=self.layer {
+            for j in 0..rows {
+                for i in 0..cols {
+                    let tile = self.map.get(layer, self.view_x + i, self.view_y + j);
+                    if let Some(sprite) = tile.and_then(|idx| self.sprite_sheet.get(idx)) {
+                        draw_tile(
+                            &mut view,
+                            sprite,
+                            self.map.cell_size,
+                            i * zoom,
+                            VIEW_Y + j * zoom,
+                            zoom,
+                        );
+                    }
+                }
+            }
+        }
+
+        if zoom >= ZOOM_LEVELS[1] {
+            for j in 0..rows {
+                for i in 0..cols {
+                    if (i + self.view_x) as usize <= self.map.width
+                        && (j + self.view_y) as usize <= self.map.height
+                    {
+                        set_pix(&mut view, VIEW_Y + j * zoom, i * zoom, Colors::Gray);
+                    }
+                }
+            }
+        }
+
+        if let Some(((x1, y1), (x2, y2))) = self.rect_drag {
+            let x = (x1.min(x2) - self.view_x) * zoom;
+            let y = VIEW_Y + (y1.min(y2) - self.view_y) * zoom;
+            let w = ((x1 - x2).abs() + 1) * zoom - 1;
+            let h = ((y1 - y2).abs() + 1) * zoom - 1;
+            rect(&mut view, x, y, w, h, Colors::White);
+        } else if let Some((cx, cy)) = hovered {
+            let x = (cx - self.view_x) * zoom;
+            let y = VIEW_Y + (cy - self.view_y) * zoom;
+            rect(&mut view, x, y, zoom - 1, zoom - 1, Colors::White);
+        }
+    }
+
+    fn text_button(&mut self, button: (i32, i32, i32, i32), text: String) -> bool {
+        let (x, y, w, h) = button;
+        rect(&mut self.pixels, x, y, w, h, Colors::Gray);
+        print_scr_mid(&mut self.pixels, x + 2, y + 2, Colors::Gray, text);
+        self.clicked(x, y, w + 1, h + 1)
+    }
+
+    fn draw_info(&mut self) {
+        let layer_text = format!("Layer {}", self.layer);
+        if self.text_button(LAYER_BUTTON, layer_text) {
+            self.layer = (self.layer + 1) % self.map.layers.len();
+        }
+
+        if self.text_button(ADD_LAYER_BUTTON, "+".to_string()) {
+            self.map.layers.push(vec![None; self.map.width * self.map.height]);
+            self.layer = self.map.layers.len() - 1;
+            self.upto_date = false;
+        }
+
+        let size_text = format!("Cell {}", self.map.cell_size);
+        if self.text_button(CELL_SIZE_BUTTON, size_text) {
+            let cur = CELL_SIZES.iter().position(|s| *s == self.map.cell_size).unwrap_or(0);
+            self.map.cell_size = CELL_SIZES[(cur + 1) % CELL_SIZES.len()];
+            self.upto_date = false;
+        }
+
+        if let Some((x, y)) = self.hovered_cell() {
+            print_scr_mid(&mut self.pixels, 92, INFO_Y, Colors::Gray, format!("{},{}", x, y));
+        }
+    }
+
+    fn draw_picker(&mut self) {
+        let mut label = "Painting tile ".to_owned() + &self.selected_tile.to_string();
+        if !self.upto_date {
+            label += "*";
+        }
+        print_scr_mid(&mut self.pixels, 4, PICKER_LABEL_Y, Colors::Gray, label);
+
+        let start = (self.picker_row * PICKER_COLS) as usize;
+        for i in 0..(PICKER_COLS * PICKER_ROWS) as usize {
+            let idx = start + i;
+            if idx >= self.sprite_sheet.len() {
+                break;
+            }
+
+            let x = (i as i32 % PICKER_COLS) * TILE_PREVIEW_SIZE;
+            let y = PICKER_Y + (i as i32 / PICKER_COLS) * TILE_PREVIEW_SIZE;
+            let (size, cell_size) = (TILE_PREVIEW_SIZE, self.map.cell_size);
+            draw_tile(&mut self.pixels, &self.sprite_sheet[idx], cell_size, x, y, size);
+            rect(&mut self.pixels, x, y, TILE_PREVIEW_SIZE, TILE_PREVIEW_SIZE, Colors::Gray);
+
+            if self.clicked(x, y, TILE_PREVIEW_SIZE, TILE_PREVIEW_SIZE) {
+                self.selected_tile = idx;
+            }
+        }
+
+        if self.selected_tile >= start {
+            let i = (self.selected_tile - start) as i32;
+            if i < PICKER_COLS * PICKER_ROWS {
+                let x = (i % PICKER_COLS) * TILE_PREVIEW_SIZE;
+                let y = PICKER_Y + (i / PICKER_COLS) * TILE_PREVIEW_SIZE;
+                rect(&mut self.pixels, x, y, TILE_PREVIEW_SIZE, TILE_PREVIEW_SIZE, Colors::White);
+            }
+        }
+    }
+
+    fn tool_button(&mut self, x: i32, y: i32, tool: MapTools) {
+        draw(&mut self.pixels, x + 1, y + 1, &image_from_map_tool(tool));
+
+        if self.clicked(x, y, BUTTON_WIDTH, BUTTON_WIDTH) {
+            self.tool = tool;
+        }
+
+        if self.tool == tool {
+            rect(&mut self.pixels, x, y, BUTTON_WIDTH - 1, BUTTON_WIDTH - 1, Colors::White);
+        }
+    }
+
+    fn save_button(&mut self) {
+        draw(&mut self.pixels, SAVE_X + 1, TOOLS_Y + 1, &image_from_util(Utils::Save));
+
+        if self.clicked(SAVE_X, TOOLS_Y, BUTTON_WIDTH, BUTTON_WIDTH) {
+            self.upto_date = true;
+            let _ = update_map(&self.map);
+        }
+    }
+
+    //Same model as the sprite editor, ctrl+z undoes and ctrl+r redoes
+    fn handle_undo_redo(&mut self) {
+        let mut t = 0;
+
+        if self.keyboard.keys_pressed.contains(&VirtualKeyCode::LControl)
+            && self.keyboard.keys_pressed.contains(&VirtualKeyCode::Z)
+        {
+            t = 1;
+        } else if self.keyboard.keys_pressed.contains(&VirtualKeyCode::LControl)
+            && self.keyboard.keys_pressed.contains(&VirtualKeyCode::R)
+        {
+            t = -1;
+        }
+
+        if t != 0 {
+            if self.last_frame_ur {
+                self.continuous_ur_frames += 1
+            } else {
+                self.continuous_ur_frames = 0
+            };
+            self.last_frame_ur = true;
+            if (self.continuous_ur_frames < UNDO_REDO_FRAME_DELAY && self.continuous_ur_frames > 0)
+                || (self.continuous_ur_frames % UNDO_REDO_CONTINUOUS_FRAME_DIVISOR != 0)
+            {
+                return;
+            }
+
+            let popped = if t == 1 { self.undo_stack.pop() } else { self.redo_stack.pop() };
+            if let Some(changes) = popped {
+                self.rect_drag = None;
+                let mut pushing: Vec<MapChange> = Vec::new();
+                for (layer, x, y, tile) in changes.into_iter().rev() {
+                    pushing.push((layer, x, y, self.map.get(layer, x, y)));
+                    self.map.set(layer, x, y, tile);
+                }
+                self.upto_date = false;
+                if t == 1 {
+                    self.redo_stack.push(pushing)
+                } else {
+                    self.undo_stack.push(pushing)
+                };
+            }
+        } else {
+            self.last_frame_ur = false;
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.frame_hash = (self.frame_hash + 1) % FRAME_HASH_MODULO;
+        sync(&mut self.last_time, FRAME_RATE);
+        clear(&mut self.pixels, Colors::Black);
+
+        for (i, tool) in
+            [MapTools::Pencil, MapTools::Rect, MapTools::Fill, MapTools::Eraser].iter().enumerate()
+        {
+            self.tool_button(4 + i as i32 * BUTTON_WIDTH, TOOLS_Y, *tool);
+        }
+        self.save_button();
+        self.draw_info();
+
+        if !self.handle_panning() {
+            self.handle_tools();
+        }
+        self.handle_undo_redo();
+
+        self.draw_view();
+        self.draw_picker();
+
+        if !self.new_changes.is_empty() {
+            self.undo_stack.push(self.new_changes.clone());
+            self.redo_stack.clear();
+            self.new_changes = Vec::new();
+        }
+    }
+}
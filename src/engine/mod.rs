@@ -1,5 +1,9 @@
+pub mod anim_strip;
 pub mod console;
+pub mod controls;
+pub mod font;
 pub mod game;
+pub mod map;
 pub mod nav_bar;
 pub mod rico;
 pub mod script;
@@ -1,31 +1,79 @@
 use macro_procs::ScreenEngine;
 
 use crate::{
-    engine::rico::{PixelsType, ScreenEngine, NAV_BAR_HEIGHT, SCREEN_SIZE},
+    engine::rico::{ScreenEngine, NAV_BAR_HEIGHT, SCREEN_SIZE},
     input::mouse::MousePress,
     render::{
         colors::Colors,
+        framebuffer::Framebuffer,
         pixels::{clear, print_scr_mid, rect_fill},
     },
 };
 
+//Where the yes and no buttons of a question start, counted back from the right edge
+const YES_X: i32 = SCREEN_SIZE as i32 - 34;
+const NO_X: i32 = SCREEN_SIZE as i32 - 15;
+
 #[derive(ScreenEngine)]
 pub struct NavEngine {
-    pixels: PixelsType,
+    pixels: Framebuffer,
     options: Vec<String>,
     pub mouse: MousePress,
     pub selected: usize,
     pub just_switched: bool,
+
+    //Tab to switch to on the next update, so just_switched lasts the whole frame
+    queued: Option<usize>,
+    //Yes or no question that takes over the bar until it's answered
+    question: Option<String>,
+    answer: Option<bool>,
 }
 
 impl NavEngine {
     pub fn new(options: Vec<String>) -> Self {
         NavEngine {
-            pixels: Colors::pixels(SCREEN_SIZE, NAV_BAR_HEIGHT),
+            pixels: Framebuffer::new(SCREEN_SIZE, NAV_BAR_HEIGHT),
             mouse: MousePress::default(),
             options,
             selected: 0,
             just_switched: false,
+            queued: None,
+            question: None,
+            answer: None,
+        }
+    }
+
+    pub fn select(&mut self, idx: usize) {
+        self.queued = Some(idx);
+    }
+
+    //Short enough to fit next to the buttons, around 20 characters
+    pub fn ask(&mut self, question: String) {
+        self.question = Some(question);
+        self.answer = None;
+    }
+
+    //Some once the question got answered, only handed out once
+    pub fn take_answer(&mut self) -> Option<bool> {
+        self.answer.take()
+    }
+
+    fn answer_button(&mut self, x: i32, text: &str) -> bool {
+        let w = text.len() as i32 * 4 + 5;
+        rect_fill(&mut self.pixels, x, 0, w, 8, Colors::Black);
+        print_scr_mid(&mut self.pixels, x + 2, 2, Colors::White, text.to_string());
+        self.mouse.just_pressed && self.mouse.x != -1 && self.mouse.x >= x && self.mouse.x <= x + w
+    }
+
+    fn draw_question(&mut self, question: String) {
+        print_scr_mid(&mut self.pixels, 2, 2, Colors::Black, question);
+        if self.answer_button(YES_X, "Yes") {
+            self.answer = Some(true);
+        } else if self.answer_button(NO_X, "No") {
+            self.answer = Some(false);
+        }
+        if self.answer.is_some() {
+            self.question = None;
         }
     }
 
@@ -35,6 +83,16 @@ impl NavEngine {
         if self.just_switched {
             self.just_switched = false
         };
+        if let Some(idx) = self.queued.take() {
+            self.just_switched = true;
+            self.selected = idx;
+        }
+
+        //Tabs can't be switched until it's answered
+        if let Some(question) = self.question.clone() {
+            self.draw_question(question);
+            return;
+        }
 
         for (i, option) in self.options.iter().enumerate() {
             if self.mouse.just_pressed
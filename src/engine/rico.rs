@@ -1,7 +1,6 @@
-use rayon::prelude::*;
 use std::{
     error::Error,
-    path::Path,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
@@ -17,11 +16,27 @@ use winit::{
     window::WindowBuilder,
 };
 
-use super::{game::GameEngine, nav_bar::NavEngine, sprite::SpriteEngine};
+use super::{
+    controls::ControlsEngine, font::FontEngine, game::GameEngine, map::MapEngine,
+    nav_bar::NavEngine, sprite::SpriteEngine,
+};
 use crate::{
-    input::{keyboard::Keyboard, mouse::MousePress},
-    render::colors::Colors,
-    scripting::cartridge::{get_cart, load_cartridge, update_scripts, PATH},
+    input::{
+        controller::Controls,
+        keyboard::Keyboard,
+        mouse::MousePress,
+        replay::{InputMode, InputSession},
+    },
+    render::{
+        colors::{ColorMap, Colors, Palette, DEFAULT_PALETTE},
+        framebuffer::{palette_lut, Framebuffer, PaletteLut},
+    },
+    scripting::{
+        cartridge::{
+            get_cart, load_cartridge, read_cart, replace_cart, update_scripts, Cartridge, PATH,
+        },
+        lua::LogTypes,
+    },
 };
 
 pub const SCREEN_SIZE: usize = 128;
@@ -30,21 +45,34 @@ pub const NAV_BAR_HEIGHT: usize = 8;
 pub const WINDOW_WIDTH: usize = SCREEN_SIZE * SCALE;
 pub const WINDOW_HEIGHT: usize = (NAV_BAR_HEIGHT + SCREEN_SIZE * 2) * SCALE;
 
+//Sprites, canvases and anything else that gets drawn from, screens use Framebuffer
 pub type PixelsType = Vec<Vec<Colors>>;
 
 /* All screen engines must implement
  * Game for now, sprite in the future, maybe IDE
  */
 pub trait ScreenEngine {
-    fn pixels(&self) -> &PixelsType;
+    fn pixels(&self) -> &Framebuffer;
+
+    fn pixels_mut(&mut self) -> &mut Framebuffer;
 
     fn reset_inputs(&mut self);
+
+    //Remap applied to the whole pane right before it goes to the window
+    fn display_palette(&self) -> &ColorMap;
+
+    //Actual rgba values for each color slot
+    fn palette(&self) -> &Palette;
 }
 
 // Make sure to box new engines, just more efficient to just store a pointer
+#[allow(clippy::enum_variant_names)]
 enum StateEngines {
     GameEngine(Box<GameEngine>),
     SpriteEngine(Box<SpriteEngine>),
+    MapEngine(Box<MapEngine>),
+    FontEngine(Box<FontEngine>),
+    ControlsEngine(Box<ControlsEngine>),
 }
 
 /* Add bindings for diff engines in this struct in the vector
@@ -54,8 +82,13 @@ enum StateEngines {
 pub struct RicoEngine {
     nav_engine: NavEngine,
     state_engines: Vec<StateEngines>,
+    //Cart that got dropped on the window, waiting on the nav bar to ok losing unsaved work
+    dropped: Option<(PathBuf, Cartridge)>,
 }
 
+//Game is always the first tab
+const GAME_TAB: usize = 0;
+
 fn watch_folder() -> Result<(), Box<dyn Error>> {
     let (tx, rx) = channel();
 
@@ -81,14 +114,81 @@ fn watch_folder() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+impl StateEngines {
+    fn invalidate(&mut self) {
+        match self {
+            StateEngines::GameEngine(eng) => {
+                eng.lua_api.borrow_mut().pixels.invalidate();
+                eng.console_engine.pixels_mut().invalidate();
+            }
+            StateEngines::SpriteEngine(eng) => eng.pixels_mut().invalidate(),
+            StateEngines::MapEngine(eng) => eng.pixels_mut().invalidate(),
+            StateEngines::FontEngine(eng) => eng.pixels_mut().invalidate(),
+            StateEngines::ControlsEngine(eng) => eng.pixels_mut().invalidate(),
+        }
+    }
+
+    //Controls aren't part of the cart so they never count
+    fn upto_date(&self) -> bool {
+        match self {
+            StateEngines::SpriteEngine(eng) => eng.upto_date(),
+            StateEngines::MapEngine(eng) => eng.upto_date(),
+            StateEngines::FontEngine(eng) => eng.upto_date(),
+            StateEngines::GameEngine(_) | StateEngines::ControlsEngine(_) => true,
+        }
+    }
+
+    //Editors start over from the new cart and the game gets swapped for one running it
+    fn load_cart(&mut self, cart: &Cartridge) {
+        let palette = cart.palette.unwrap_or(DEFAULT_PALETTE);
+        match self {
+            StateEngines::GameEngine(eng) => {
+                let mode = eng.input.mode().for_other_cart();
+                eng.replace(cart.clone(), mode);
+            }
+            StateEngines::SpriteEngine(eng) => {
+                **eng = SpriteEngine::new(
+                    cart.sprite_sheet.clone(),
+                    cart.sprite_info.clone(),
+                    cart.animations.clone(),
+                    palette,
+                );
+            }
+            StateEngines::MapEngine(eng) => {
+                **eng = MapEngine::new(cart.map.clone(), cart.sprite_sheet.clone(), palette);
+            }
+            StateEngines::FontEngine(eng) => **eng = FontEngine::new(cart.fonts.clone()),
+            StateEngines::ControlsEngine(_) => {}
+        }
+    }
+}
+
 impl Default for RicoEngine {
     fn default() -> Self {
+        RicoEngine::new(InputMode::Live)
+    }
+}
+
+impl RicoEngine {
+    pub fn new(input: InputMode) -> Self {
         let cart = load_cartridge().expect("Could not load/create cartridge");
-        let sprite_eng = SpriteEngine::new(cart.sprite_sheet.clone());
-        let game_eng = GameEngine::new(cart);
+        let palette = cart.palette.unwrap_or(DEFAULT_PALETTE);
+        let sprite_eng = SpriteEngine::new(
+            cart.sprite_sheet.clone(),
+            cart.sprite_info.clone(),
+            cart.animations.clone(),
+            palette,
+        );
+        let map_eng = MapEngine::new(cart.map.clone(), cart.sprite_sheet.clone(), palette);
+        let font_eng = FontEngine::new(cart.fonts.clone());
+        let controls_eng = ControlsEngine::new(Controls::load());
+        let game_eng = GameEngine::with_input(cart, input);
         let state_engines = vec![
             StateEngines::GameEngine(Box::new(game_eng)),
             StateEngines::SpriteEngine(Box::new(sprite_eng)),
+            StateEngines::MapEngine(Box::new(map_eng)),
+            StateEngines::FontEngine(Box::new(font_eng)),
+            StateEngines::ControlsEngine(Box::new(controls_eng)),
         ];
 
         std::thread::spawn(|| {
@@ -97,13 +197,69 @@ impl Default for RicoEngine {
 
         //Change here if want diff names for engines
         RicoEngine {
-            nav_engine: NavEngine::new(vec!["Game".to_string(), "Sprite".to_string()]),
+            nav_engine: NavEngine::new(vec![
+                "Game".to_string(),
+                "Sprite".to_string(),
+                "Map".to_string(),
+                "Font".to_string(),
+                "Keys".to_string(),
+            ]),
             state_engines,
+            dropped: None,
+        }
+    }
+
+    //Logs end up in the game's console, the only place there is to show them
+    fn log(&mut self, log: LogTypes) {
+        let game = self.state_engines.iter_mut().find_map(|eng| match eng {
+            StateEngines::GameEngine(eng) => Some(eng),
+            _ => None,
+        });
+        if let Some(game) = game {
+            game.lua_api.borrow_mut().add_log(log);
+        }
+    }
+
+    //Checked before anything happens, unsaved editor work only gets thrown out if the nav bar says so
+    fn drop_cart(&mut self, path: PathBuf) {
+        match read_cart(&path).and_then(Cartridge::validated) {
+            Ok(cart) if self.state_engines.iter().all(StateEngines::upto_date) => {
+                self.load_cart(&path, cart);
+            }
+            Ok(cart) => {
+                self.nav_engine.ask("Lose unsaved changes?".to_string());
+                self.dropped = Some((path, cart));
+            }
+            Err(err) => {
+                self.log(LogTypes::Err(format!("Could not load {}: {err}", path.display())));
+                self.nav_engine.select(GAME_TAB);
+            }
+        }
+    }
+
+    /* Dropped carts become main.r32 so saving and the script watcher keep working on them
+     * the old main.r32 gets copied next to it first
+     */
+    fn load_cart(&mut self, path: &Path, cart: Cartridge) {
+        self.nav_engine.select(GAME_TAB);
+        let loaded = replace_cart(&cart).and_then(|backup| Ok((backup, load_cartridge()?)));
+        let (backup, cart) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                self.log(LogTypes::Err(format!("Could not load {}: {err}", path.display())));
+                return;
+            }
+        };
+
+        for eng in self.state_engines.iter_mut() {
+            eng.load_cart(&cart);
+        }
+        self.log(LogTypes::Ok(format!("Loaded {}", path.display())));
+        if let Some(backup) = backup {
+            self.log(LogTypes::Ok(format!("Old cart kept as {}", backup.display())));
         }
     }
-}
 
-impl RicoEngine {
     //Base boot function, needs to take in whole self cause borrowing bs
     pub fn start(mut self) -> Result<(), Box<dyn std::error::Error>> {
         let event_loop = EventLoop::new();
@@ -116,6 +272,7 @@ impl RicoEngine {
         let surface_texture =
             SurfaceTexture::new(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32, &window);
         let mut pixels = Pixels::new(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32, surface_texture)?;
+        let mut os_cursor_hidden = false;
 
         // Event loop: Poll so we run as fast as possible and continuously request redraws
         event_loop.run(move |event, _, control_flow| {
@@ -128,6 +285,12 @@ impl RicoEngine {
                     let buffer = pixels.frame_mut();
                     self.update(buffer);
 
+                    let hide = self.hides_os_cursor();
+                    if hide != os_cursor_hidden {
+                        window.set_cursor_visible(!hide);
+                        os_cursor_hidden = hide;
+                    }
+
                     if pixels.render().is_err() {
                         *control_flow = ControlFlow::Exit;
                     }
@@ -138,20 +301,43 @@ impl RicoEngine {
                     window.request_redraw();
                 }
 
+                //Recordings only get closed properly here, so quitting any way still saves them
+                Event::LoopDestroyed => {
+                    for eng in self.state_engines.iter_mut() {
+                        if let StateEngines::GameEngine(eng) = eng {
+                            if let Some(saved) = eng.stop_input_recording() {
+                                println!("{saved}");
+                            }
+                        }
+                    }
+                }
+
                 Event::WindowEvent { event, .. } => match event {
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
 
+                    WindowEvent::DroppedFile(path) => self.drop_cart(path),
+
                     WindowEvent::KeyboardInput { input, .. } => {
                         if let Some(keycode) = input.virtual_keycode {
                             //Use match for finding which engine we're using rn
                             match self.state_engines[self.nav_engine.selected] {
                                 StateEngines::GameEngine(ref mut eng) => {
                                     let mut lua_api = eng.lua_api.borrow_mut();
+                                    lua_api.capture_hotkey(input.state, keycode);
                                     bind_keyboard(&mut lua_api.keyboard, input.state, keycode);
                                 }
                                 StateEngines::SpriteEngine(ref mut eng) => {
                                     bind_keyboard(&mut eng.keyboard, input.state, keycode);
                                 }
+                                StateEngines::MapEngine(ref mut eng) => {
+                                    bind_keyboard(&mut eng.keyboard, input.state, keycode);
+                                }
+                                StateEngines::FontEngine(ref mut eng) => {
+                                    bind_keyboard(&mut eng.keyboard, input.state, keycode);
+                                }
+                                StateEngines::ControlsEngine(ref mut eng) => {
+                                    bind_keyboard(&mut eng.keyboard, input.state, keycode);
+                                }
                             }
 
                             // exit on ESC
@@ -161,18 +347,39 @@ impl RicoEngine {
                         }
                     }
 
+                    WindowEvent::ReceivedCharacter(c) => {
+                        match self.state_engines[self.nav_engine.selected] {
+                            StateEngines::GameEngine(ref mut eng) => {
+                                bind_text(&mut eng.lua_api.borrow_mut().keyboard, c);
+                            }
+                            StateEngines::FontEngine(ref mut eng) => {
+                                bind_text(&mut eng.keyboard, c);
+                            }
+                            _ => {}
+                        }
+                    }
+
                     WindowEvent::MouseWheel { delta, .. } => {
-                        //If let cause we only need scroll wheels for sprite rn, switch to match
-                        //later
-                        if let StateEngines::SpriteEngine(ref mut eng) =
-                            self.state_engines[self.nav_engine.selected]
-                        {
-                            let scroll_y = match delta {
-                                MouseScrollDelta::LineDelta(_, y) => y,
-                                MouseScrollDelta::PixelDelta(pos) => pos.y as f32,
-                            };
-
-                            eng.update_start_row(scroll_y);
+                        let scroll_y = wheel_lines(delta);
+
+                        match self.state_engines[self.nav_engine.selected] {
+                            StateEngines::GameEngine(ref mut eng) => {
+                                let mut lua_api = eng.lua_api.borrow_mut();
+                                //Only counts while the cursor is over the game
+                                if lua_api.mouse.x != -1 {
+                                    lua_api.mouse.wheel += scroll_y;
+                                }
+                            }
+                            StateEngines::SpriteEngine(ref mut eng) => {
+                                eng.update_start_row(scroll_y);
+                            }
+                            StateEngines::MapEngine(ref mut eng) => {
+                                eng.scroll(scroll_y);
+                            }
+                            StateEngines::FontEngine(ref mut eng) => {
+                                eng.scroll(scroll_y);
+                            }
+                            StateEngines::ControlsEngine(_) => {}
                         }
                     }
 
@@ -191,6 +398,15 @@ impl RicoEngine {
                             StateEngines::SpriteEngine(ref mut eng) => {
                                 bind_mouse_input(&mut eng.mouse, button, state);
                             }
+                            StateEngines::MapEngine(ref mut eng) => {
+                                bind_mouse_input(&mut eng.mouse, button, state);
+                            }
+                            StateEngines::FontEngine(ref mut eng) => {
+                                bind_mouse_input(&mut eng.mouse, button, state);
+                            }
+                            StateEngines::ControlsEngine(ref mut eng) => {
+                                bind_mouse_input(&mut eng.mouse, button, state);
+                            }
                         };
                     }
 
@@ -237,6 +453,59 @@ impl RicoEngine {
                                     WINDOW_WIDTH * 2,
                                 );
                             }
+                            StateEngines::MapEngine(ref mut eng) => {
+                                bind_mouse_move(
+                                    &mut eng.mouse,
+                                    logical,
+                                    0,
+                                    NAV_BAR_HEIGHT * SCALE,
+                                    WINDOW_WIDTH,
+                                    WINDOW_WIDTH * 2,
+                                );
+                            }
+                            StateEngines::FontEngine(ref mut eng) => {
+                                bind_mouse_move(
+                                    &mut eng.mouse,
+                                    logical,
+                                    0,
+                                    NAV_BAR_HEIGHT * SCALE,
+                                    WINDOW_WIDTH,
+                                    WINDOW_WIDTH * 2,
+                                );
+                            }
+                            StateEngines::ControlsEngine(ref mut eng) => {
+                                bind_mouse_move(
+                                    &mut eng.mouse,
+                                    logical,
+                                    0,
+                                    NAV_BAR_HEIGHT * SCALE,
+                                    WINDOW_WIDTH,
+                                    WINDOW_WIDTH * 2,
+                                );
+                            }
+                        }
+                    }
+
+                    //Otherwise the last spot inside stays, drawn cursors included
+                    WindowEvent::CursorLeft { .. } => {
+                        bind_mouse_left(&mut self.nav_engine.mouse);
+                        match self.state_engines[self.nav_engine.selected] {
+                            StateEngines::GameEngine(ref mut eng) => {
+                                bind_mouse_left(&mut eng.lua_api.borrow_mut().mouse);
+                                bind_mouse_left(&mut eng.console_engine.mouse);
+                            }
+                            StateEngines::SpriteEngine(ref mut eng) => {
+                                bind_mouse_left(&mut eng.mouse);
+                            }
+                            StateEngines::MapEngine(ref mut eng) => {
+                                bind_mouse_left(&mut eng.mouse);
+                            }
+                            StateEngines::FontEngine(ref mut eng) => {
+                                bind_mouse_left(&mut eng.mouse);
+                            }
+                            StateEngines::ControlsEngine(ref mut eng) => {
+                                bind_mouse_left(&mut eng.mouse);
+                            }
                         }
                     }
 
@@ -247,15 +516,57 @@ impl RicoEngine {
         });
     }
 
+    //Panes that draw their own cursor hide the real one while it's over them
+    fn hides_os_cursor(&self) -> bool {
+        match &self.state_engines[self.nav_engine.selected] {
+            StateEngines::GameEngine(eng) => eng.hides_os_cursor(),
+            StateEngines::SpriteEngine(eng) => eng.cursor.hides_os(),
+            _ => false,
+        }
+    }
+
     //Make sure to update engines here based on which screen it's on
     pub fn update(&mut self, buffer: &mut [u8]) {
         self.nav_engine.update();
         handle_engine_update(buffer, &mut self.nav_engine, 0, 0);
 
+        //Either answer is done with the dropped cart, only yes loads it
+        if let Some(yes) = self.nav_engine.take_answer() {
+            if let Some((path, cart)) = self.dropped.take().filter(|_| yes) {
+                self.load_cart(&path, cart);
+            }
+        }
+
+        //Map editor paints with whatever is in the sprite editor, saved or not
+        let sprites = self.state_engines.iter().find_map(|eng| match eng {
+            StateEngines::SpriteEngine(eng) if self.nav_engine.just_switched => {
+                Some((eng.sprite_sheet.clone(), eng.palette))
+            }
+            _ => None,
+        });
+
+        //Games pick up rebound keys as soon as they get switched back to
+        let controls = self.state_engines.iter().find_map(|eng| match eng {
+            StateEngines::ControlsEngine(eng) if self.nav_engine.just_switched => {
+                Some(eng.controls.clone())
+            }
+            _ => None,
+        });
+
+        //Tabs share the same part of the window, so whatever got switched to redraws fully
+        if self.nav_engine.just_switched {
+            self.state_engines[self.nav_engine.selected].invalidate();
+        }
+
         match self.state_engines[self.nav_engine.selected] {
             StateEngines::GameEngine(ref mut eng) => {
                 if self.nav_engine.just_switched {
                     eng.console_engine.last_time = Instant::now();
+                    //Replays bring their own controls along
+                    let replaying = matches!(eng.input, InputSession::Replaying(_));
+                    if let Some(controls) = controls.filter(|_| !replaying) {
+                        eng.lua_api.borrow_mut().controls = controls;
+                    }
                 }
 
                 eng.update();
@@ -272,14 +583,34 @@ impl RicoEngine {
 
                 if console.restart {
                     let cart = get_cart().expect("Could not load/create cartridge");
-                    let game_eng = GameEngine::new(cart);
-                    **eng = game_eng;
+                    //Recordings get saved and a new one starts, replays play again from the top
+                    let mode = eng.input.mode();
+                    eng.replace(cart, mode);
                 }
             }
             StateEngines::SpriteEngine(ref mut eng) => {
                 eng.update();
                 handle_engine_update(buffer, &mut **eng, 0, NAV_BAR_HEIGHT * SCALE);
             }
+            StateEngines::MapEngine(ref mut eng) => {
+                if self.nav_engine.just_switched {
+                    if let Some((sheet, palette)) = &sprites {
+                        eng.sprite_sheet = sheet.clone();
+                        eng.palette = *palette;
+                    }
+                }
+
+                eng.update();
+                handle_engine_update(buffer, &mut **eng, 0, NAV_BAR_HEIGHT * SCALE);
+            }
+            StateEngines::FontEngine(ref mut eng) => {
+                eng.update();
+                handle_engine_update(buffer, &mut **eng, 0, NAV_BAR_HEIGHT * SCALE);
+            }
+            StateEngines::ControlsEngine(ref mut eng) => {
+                eng.update();
+                handle_engine_update(buffer, &mut **eng, 0, NAV_BAR_HEIGHT * SCALE);
+            }
         }
     }
 }
@@ -292,46 +623,41 @@ pub fn handle_engine_update(
     start_y: usize,
 ) {
     //Uses screen engine implementations to actually render that specific engine
-    let pixels = eng.pixels();
-    copy_pixels_into_buffer(pixels, buffer, start_x, start_y);
+    //Panes that haven't changed since last frame are already in the window buffer
+    let lut = palette_lut(eng.palette(), eng.display_palette());
+    let pixels = eng.pixels_mut();
+    if pixels.needs_present(&lut) {
+        copy_pixels_into_buffer(pixels, &lut, buffer, start_x, start_y);
+        pixels.mark_presented(lut);
+    }
     eng.reset_inputs();
 }
 
-/* IMPORTANT:
- * Currently parallalized with rayon but its lowkey useless
- * It spends half the time just switching mutex locks so we might wanna just single
- * thread this. Shouldn't change too much, we're pretty efficient alr.
+/* Scales each row straight into the window buffer, then copies that row down
+ * for the rest of the SCALE rows, no temp buffer or per pixel palette work
  */
 pub fn copy_pixels_into_buffer(
-    pixels: &PixelsType,
+    pixels: &Framebuffer,
+    lut: &PaletteLut,
     buffer: &mut [u8],
     start_x: usize,
     start_y: usize,
 ) {
-    let height = pixels.len();
-    let width = pixels[0].len();
-
-    let mut buf_tmp = vec![0u8; width * height * SCALE * SCALE * 4];
-
-    buf_tmp.par_chunks_mut(width * SCALE * 4).enumerate().for_each(|(out_y, row)| {
-        let src_y = out_y / SCALE;
-
-        for (x, pix) in pixels[src_y].iter().enumerate().take(width) {
-            let (r, g, b, a) = pix.rgba();
-            let base = x * SCALE * 4;
-            for dx in 0..SCALE {
-                let i = base + dx * 4;
-                row[i..i + 4].copy_from_slice(&[r, g, b, a]);
+    let row_len = pixels.width() * SCALE * 4;
+
+    for (y, row) in pixels.rows().enumerate() {
+        let dst = ((start_y + y * SCALE) * WINDOW_WIDTH + start_x) * 4;
+        let out = &mut buffer[dst..dst + row_len];
+        for (pix, block) in row.iter().zip(out.chunks_exact_mut(SCALE * 4)) {
+            let rgba = &lut[*pix as usize];
+            for out_pix in block.chunks_exact_mut(4) {
+                out_pix.copy_from_slice(rgba);
             }
         }
-    });
 
-    for y in 0..height * SCALE {
-        let dst_row = ((start_y + y) * WINDOW_WIDTH + start_x) * 4;
-        let src_row = y * width * SCALE * 4;
-
-        buffer[dst_row..dst_row + width * SCALE * 4]
-            .copy_from_slice(&buf_tmp[src_row..src_row + width * SCALE * 4]);
+        for dy in 1..SCALE {
+            buffer.copy_within(dst..dst + row_len, dst + dy * WINDOW_WIDTH * 4);
+        }
     }
 }
 
@@ -345,28 +671,56 @@ pub fn bind_keyboard(keyboard: &mut Keyboard, state: ElementState, keycode: Virt
             keyboard.keys_pressed.insert(keycode);
         }
         ElementState::Released => {
-            keyboard.keys_pressed.remove(&keycode);
+            if keyboard.keys_pressed.remove(&keycode) {
+                keyboard.keys_just_released.insert(keycode);
+            }
         }
     }
 }
 
+//Control characters like backspace and enter are left to the key functions
+pub fn bind_text(keyboard: &mut Keyboard, c: char) {
+    if !c.is_control() {
+        keyboard.text.push(c);
+    }
+}
+
 //Im so sad this doesn't give me access to mouse position, it'd be sm easier to
 //do the -1, -1 thing just here
 pub fn bind_mouse_input(mouse: &mut MousePress, button: MouseButton, state: ElementState) {
-    if button == MouseButton::Left {
-        match state {
-            ElementState::Pressed => {
-                mouse.pressed = true;
-                mouse.just_pressed = true;
-            }
-            ElementState::Released => {
-                mouse.pressed = false;
-                mouse.just_pressed = false;
-            }
-        }
+    let mut left = mouse.left();
+    let target = match button {
+        MouseButton::Left => &mut left,
+        MouseButton::Right => &mut mouse.right,
+        MouseButton::Middle => &mut mouse.middle,
+        MouseButton::Other(_) => return,
+    };
+    match state {
+        ElementState::Pressed => target.press(),
+        ElementState::Released => target.release(),
+    }
+    mouse.set_left(left);
+}
+
+//Touchpads scroll in pixels, roughly a line per WHEEL_PIXELS of them so lua sees lines either way
+const WHEEL_PIXELS: f32 = 20.0;
+
+pub fn wheel_lines(delta: MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => y,
+        MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / WHEEL_PIXELS,
     }
 }
 
+//Mouse went off the engine or out of the window altogether
+pub fn bind_mouse_left(mouse: &mut MousePress) {
+    mouse.release_all();
+    mouse.x = -1;
+    mouse.y = -1;
+    mouse.fx = -1.0;
+    mouse.fy = -1.0;
+}
+
 pub fn check_mouse_bounds(
     mouse: &mut MousePress,
     start_x: usize,
@@ -377,10 +731,7 @@ pub fn check_mouse_bounds(
     let cur_x = mouse.x as usize;
     let cur_y = mouse.y as usize;
     if cur_x < start_x || cur_x > start_x + width || cur_y < start_y || cur_y > start_y + height {
-        mouse.pressed = false;
-        mouse.just_pressed = false;
-        mouse.x = -1;
-        mouse.y = -1;
+        bind_mouse_left(mouse);
         return true;
     }
 
@@ -399,6 +750,8 @@ pub fn bind_mouse_move(
     mouse.y = logical_position.y as i32;
 
     if !check_mouse_bounds(mouse, start_x, start_y, width, height) {
+        mouse.fx = (logical_position.x - start_x as f32) / SCALE as f32;
+        mouse.fy = (logical_position.y - start_y as f32) / SCALE as f32;
         mouse.x -= start_x as i32;
         mouse.y -= start_y as i32;
 
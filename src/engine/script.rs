@@ -31,6 +31,13 @@ impl ScriptEngine {
         Ok(())
     }
 
+    //Same seed gives the same math.random rolls, replays need that to play out the same
+    pub fn seed_random(&self, seed: u64) -> LuaResult<()> {
+        let math: LuaTable = self.lua.globals().get("math")?;
+        let randomseed: LuaFunction = math.get("randomseed")?;
+        randomseed.call(seed as i64)
+    }
+
     /* Only way I could think to allow modularization
      * Just a wrapper to load in lua scripts and insert into main
      * Keeps Lua API the same
@@ -4,15 +4,25 @@ use macro_procs::ScreenEngine;
 use winit::event::VirtualKeyCode;
 
 use crate::{
-    engine::rico::{PixelsType, ScreenEngine, SCREEN_SIZE},
+    engine::{
+        anim_strip::AnimStrip,
+        rico::{PixelsType, ScreenEngine, SCREEN_SIZE},
+    },
     input::{keyboard::Keyboard, mouse::MousePress},
     render::{
-        colors::{Colors, ALL_COLORS},
+        colors::{Colors, Palette, ALL_COLORS, DEFAULT_PALETTE},
+        cursor::{Cursor, CursorLayer},
+        framebuffer::Framebuffer,
         pixels::{
-            clear, draw, image_from_tool, image_from_util, print_scr_mid, rect, rect_fill, set_pix,
+            clear, draw, flood_region, image_from_tool, image_from_util, print_scr_mid, rect,
+            rect_fill, set_pix,
         },
     },
-    scripting::cartridge::update_sprites,
+    scripting::{
+        animation::Animation,
+        cartridge::{update_animations, update_palette, update_sprite_info, update_sprites},
+        sprite_info::{fit_sprite_info, SpriteInfo, SPRITE_FLAGS},
+    },
     time::sync,
 };
 
@@ -22,6 +32,8 @@ pub enum Tools {
     Fill,
     Eraser,
     Select,
+    Pivot,
+    Hitbox,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -29,78 +41,130 @@ pub enum Utils {
     FlipHor,
     FlipVert,
     Clear,
+    Palette,
+    Animation,
     Save,
 }
 
 //I SWEAR THIS IS BETTER THAN ALL THE MAGIC NUMBERS
 pub const BUTTON_WIDTH: i32 = 12;
+//Size new sprites start at, each sprite can be any of SPRITE_SIZES after that
 pub const SPRITE_SIZE: usize = 32;
-const DRAW_Y: i32 = 52;
+pub const SPRITE_SIZES: [usize; 4] = [8, 16, 32, 64];
+pub const DRAW_Y: i32 = 52;
 const FRAME_RATE: i32 = 60;
-const PIXEL_SIZE: i32 = 3;
-const CANVAS_X: i32 = 16;
+//Square the canvas zooms the sprite to fit, in whole screen pixels per sprite pixel
+pub const CANVAS_SIZE: i32 = 96;
+pub const CANVAS_X: i32 = 16;
 const COLORS_PER_ROW: i32 = 8;
 const COLOR_PALETTE_Y: i32 = 10;
 const TOOLS_Y: i32 = 154;
 const UTILS_X: i32 = 64;
+const ANIM_X: i32 = 52;
 const SAVE_X: i32 = 112;
 const SPRITESHEET_Y: i32 = 174;
-const SPRITESHEET_COLS: i32 = 6;
+pub const SPRITESHEET_COLS: i32 = 6;
 const SPRITESHEET_ROWS: i32 = 4;
 const SPRITE_PREVIEW_SIZE: i32 = 16;
 const ADD_SPRITE_BUTTON_Y: i32 = 242;
 const ADD_SPRITE_BUTTON_SIZE: i32 = 9;
-const UNDO_REDO_FRAME_DELAY: i32 = 25;
-const UNDO_REDO_CONTINUOUS_FRAME_DIVISOR: i32 = 2;
-const FRAME_HASH_MODULO: i32 = 7;
+pub const UNDO_REDO_FRAME_DELAY: i32 = 25;
+pub const UNDO_REDO_CONTINUOUS_FRAME_DIVISOR: i32 = 2;
+pub const FRAME_HASH_MODULO: i32 = 7;
 const SPRITES_TO_ADD: usize = 6;
-
-const DIRS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const SWATCH_HEIGHT: i32 = 28;
+const SLIDER_X: i32 = CANVAS_X + 8;
+const SLIDER_Y: i32 = DRAW_Y + 36;
+const SLIDER_WIDTH: i32 = 64;
+const SLIDER_HEIGHT: i32 = 5;
+const SLIDER_GAP: i32 = 12;
+const RESET_BUTTON: (i32, i32, i32, i32) = (CANVAS_X, SLIDER_Y + SLIDER_GAP * 3 + 2, 25, 9);
+//Column right of the canvas with the flags, then the pivot and hitbox tools
+const META_X: i32 = CANVAS_X + CANVAS_SIZE + 2;
+const FLAG_SIZE: i32 = 5;
+const FLAG_GAP: i32 = 6;
+const FLAG_COLORS: [Colors; SPRITE_FLAGS] = [
+    Colors::Red,
+    Colors::Orange,
+    Colors::Yellow,
+    Colors::Green,
+    Colors::Blue,
+    Colors::Purple,
+    Colors::Pink,
+    Colors::White,
+];
+const PIVOT_BUTTON_Y: i32 = DRAW_Y + FLAG_GAP * SPRITE_FLAGS as i32 + 6;
+const HITBOX_BUTTON_Y: i32 = PIVOT_BUTTON_Y + BUTTON_WIDTH + 2;
+const SIZE_Y: i32 = DRAW_Y - 16;
+const SIZE_BUTTON_WIDTH: i32 = 14;
+const SCROLL_BAR_WIDTH: i32 = 3;
 
 type MoveInfoType = Option<((i32, i32), (i32, i32, i32, i32))>;
+//Sprite, y, x and the color it had before
+type SpriteChange = (usize, usize, usize, Colors);
 
 #[derive(ScreenEngine)]
 pub struct SpriteEngine {
-    pixels: PixelsType,
+    pixels: Framebuffer,
     selected_color: Colors,
     pub mouse: MousePress,
     pub sprite_sheet: Vec<PixelsType>,
+    pub sprite_info: Vec<SpriteInfo>,
+    pub anim_strip: AnimStrip,
+    pub palette: Palette,
     pub tool: Tools,
     pub keyboard: Keyboard,
+    pub cursor: Cursor,
+    cursor_layer: CursorLayer,
 
     selection: Option<(i32, i32, i32, i32)>,
     selection_start_pos: Option<(i32, i32)>,
     moving_selection_content: Option<PixelsType>,
     move_start_info: MoveInfoType,
+    hitbox_start: Option<(i32, i32)>,
 
     copied_content: Option<PixelsType>,
 
-    new_changes: Vec<(usize, usize, Colors)>,
-    undo_stack: Vec<Vec<(usize, usize, Colors)>>,
-    redo_stack: Vec<Vec<(usize, usize, Colors)>>,
+    new_changes: Vec<SpriteChange>,
+    undo_stack: Vec<Vec<SpriteChange>>,
+    redo_stack: Vec<Vec<SpriteChange>>,
     last_frame_ur: bool,
     continuous_ur_frames: i32,
 
     last_time: Instant,
     idx: usize,
     upto_date: bool,
+    editing_palette: bool,
+    editing_anims: bool,
     start_row: i32,
     frame_hash: i32,
 }
 
 impl SpriteEngine {
-    pub fn new(sprite_sheet: Vec<PixelsType>) -> Self {
+    pub fn new(
+        sprite_sheet: Vec<PixelsType>,
+        mut sprite_info: Vec<SpriteInfo>,
+        animations: Vec<Animation>,
+        palette: Palette,
+    ) -> Self {
+        fit_sprite_info(&mut sprite_info, sprite_sheet.len());
         SpriteEngine {
-            pixels: Colors::pixels(SCREEN_SIZE, SCREEN_SIZE * 2),
+            pixels: Framebuffer::new(SCREEN_SIZE, SCREEN_SIZE * 2),
             mouse: MousePress::default(),
             selected_color: Colors::Black,
             sprite_sheet,
+            sprite_info,
+            anim_strip: AnimStrip::new(animations),
+            palette,
             tool: Tools::Pencil,
             selection: None,
             selection_start_pos: None,
             moving_selection_content: None,
             move_start_info: None,
+            hitbox_start: None,
             keyboard: Keyboard::default(),
+            cursor: Cursor::Os,
+            cursor_layer: CursorLayer::default(),
             copied_content: None,
             new_changes: Vec::new(),
             undo_stack: Vec::new(),
@@ -110,35 +174,66 @@ impl SpriteEngine {
             last_time: Instant::now(),
             idx: 0,
             upto_date: true,
+            editing_palette: false,
+            editing_anims: false,
             start_row: 0,
             frame_hash: 0,
         }
     }
 
+    pub fn upto_date(&self) -> bool {
+        self.upto_date
+    }
+
     fn set_pix(&mut self, y: usize, x: usize, col: Colors) {
         if self.sprite_sheet[self.idx][y][x] == col {
             return;
         }
-        self.new_changes.push((y, x, self.sprite_sheet[self.idx][y][x]));
+        self.new_changes.push((self.idx, y, x, self.sprite_sheet[self.idx][y][x]));
         self.upto_date = false;
         self.sprite_sheet[self.idx][y][x] = col;
     }
 
+    //Flags, pivot and hitbox don't go through undo, just mark the sheet as unsaved
+    fn edit_info<F: FnOnce(&mut SpriteInfo)>(&mut self, edit: F) {
+        let info = &mut self.sprite_info[self.idx];
+        let before = *info;
+        edit(info);
+        if *info != before {
+            self.upto_date = false;
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.sprite_sheet[self.idx].len()
+    }
+
+    fn zoom(&self) -> i32 {
+        (CANVAS_SIZE / self.size() as i32).max(1)
+    }
+
+    //Top left of the sprite on screen, anything that doesn't fill the canvas gets centered
+    fn canvas_origin(&self) -> (i32, i32) {
+        let pad = (CANVAS_SIZE - self.size() as i32 * self.zoom()) / 2;
+        (CANVAS_X + pad, DRAW_Y + pad)
+    }
+
     fn stamp_selection(&mut self) {
         if let (Some(mut content), Some((x1, y1, _, _))) =
             (self.moving_selection_content.take(), self.selection)
         {
             let h = content.len();
             let w = content[0].len();
+            let size = self.size();
 
             for (r, row) in content.iter_mut().enumerate().take(h) {
                 for (c, col) in row.iter_mut().enumerate().take(w) {
                     let target_y = y1 as isize + r as isize;
                     let target_x = x1 as isize + c as isize;
                     if target_y >= 0
-                        && target_y < SPRITE_SIZE as isize
+                        && target_y < size as isize
                         && target_x >= 0
-                        && target_x < SPRITE_SIZE as isize
+                        && target_x < size as isize
                     {
                         self.set_pix(target_y as usize, target_x as usize, *col);
                     }
@@ -154,62 +249,46 @@ impl SpriteEngine {
                 self.set_pix(y, x, self.selected_color);
             }
             Tools::Fill => {
-                let col = self.sprite_sheet[self.idx][y][x];
-                let mut q: Vec<(i32, i32)> = vec![(y as i32, x as i32)];
-                let mut visited: [[bool; SPRITE_SIZE]; SPRITE_SIZE] =
-                    [[false; SPRITE_SIZE]; SPRITE_SIZE];
-                while let Some(t) = q.pop() {
-                    if visited[t.0 as usize][t.1 as usize] {
-                        continue;
-                    }
-                    visited[t.0 as usize][t.1 as usize] = true;
-                    self.set_pix(t.0 as usize, t.1 as usize, self.selected_color);
-
-                    for dir in DIRS {
-                        let ny = t.0 + dir.0;
-                        let nx = t.1 + dir.1;
-                        if ny >= 0
-                            && ny < SPRITE_SIZE as i32
-                            && nx >= 0
-                            && nx < SPRITE_SIZE as i32
-                            && col == self.sprite_sheet[self.idx][ny as usize][nx as usize]
-                        {
-                            q.push((ny, nx));
-                        }
-                    }
+                for (ry, rx) in flood_region(&self.sprite_sheet[self.idx], y as i32, x as i32) {
+                    self.set_pix(ry as usize, rx as usize, self.selected_color);
                 }
             }
             Tools::Eraser => {
                 self.set_pix(y, x, Colors::Blank);
             }
-            Tools::Select => {}
+            Tools::Pivot => {
+                self.edit_info(|info| info.pivot = Some((x as i32, y as i32)));
+            }
+            Tools::Select | Tools::Hitbox => {}
         };
     }
 
     fn draw_canvas(&mut self) {
-        for y in 0..SPRITE_SIZE as i32 {
-            for x in 0..SPRITE_SIZE as i32 {
+        let size = self.size() as i32;
+        let zoom = self.zoom();
+        let (ox, oy) = self.canvas_origin();
+        for y in 0..size {
+            for x in 0..size {
                 let mut col = self.sprite_sheet[self.idx][y as usize][x as usize];
                 if col == Colors::Blank {
                     col = if (y + x) % 2 == 0 { Colors::Silver } else { Colors::White };
                 }
-                rect_fill(
-                    &mut self.pixels,
-                    CANVAS_X + x * PIXEL_SIZE,
-                    DRAW_Y + y * PIXEL_SIZE,
-                    PIXEL_SIZE,
-                    PIXEL_SIZE,
-                    col,
-                );
+                rect_fill(&mut self.pixels, ox + x * zoom, oy + y * zoom, zoom, zoom, col);
             }
         }
 
-        let on_canvas = self.mouse.x >= CANVAS_X
-            && self.mouse.x < CANVAS_X + (SPRITE_SIZE as i32 * PIXEL_SIZE)
-            && self.mouse.y >= DRAW_Y
-            && self.mouse.y < DRAW_Y + (SPRITE_SIZE as i32 * PIXEL_SIZE);
-        let grid_x = ((self.mouse.x - CANVAS_X) / PIXEL_SIZE).max(0).min(SPRITE_SIZE as i32 - 1);
-        let grid_y = ((self.mouse.y - DRAW_Y) / PIXEL_SIZE).max(0).min(SPRITE_SIZE as i32 - 1);
+        let on_canvas = self.mouse.x >= ox
+            && self.mouse.x < ox + size * zoom
+            && self.mouse.y >= oy
+            && self.mouse.y < oy + size * zoom;
+        //Crosshair shows exactly which pixel gets painted, selections just get pointed at
+        self.cursor = match (on_canvas, self.tool) {
+            (false, _) => Cursor::Os,
+            (true, Tools::Select) => Cursor::Pointer,
+            (true, _) => Cursor::Crosshair,
+        };
+        let grid_x = ((self.mouse.x - ox) / zoom).clamp(0, size - 1);
+        let grid_y = ((self.mouse.y - oy) / zoom).clamp(0, size - 1);
 
         if self.tool == Tools::Select {
             if self.mouse.just_pressed && on_canvas {
@@ -273,8 +352,8 @@ impl SpriteEngine {
                 for (r, row) in content.iter().enumerate().take(h) {
                     for (c, col) in row.iter().enumerate().take(w) {
                         let mut color = col;
-                        let draw_x = CANVAS_X + (x1 + c as i32) * PIXEL_SIZE;
-                        let draw_y = DRAW_Y + (y1 + r as i32) * PIXEL_SIZE;
+                        let draw_x = ox + (x1 + c as i32) * zoom;
+                        let draw_y = oy + (y1 + r as i32) * zoom;
 
                         if *color == Colors::Blank {
                             color = if (draw_y + draw_x) % 2 == 0 {
@@ -283,20 +362,23 @@ impl SpriteEngine {
                                 &Colors::White
                             };
                         }
-                        rect_fill(&mut self.pixels, draw_x, draw_y, PIXEL_SIZE, PIXEL_SIZE, *color);
+                        rect_fill(&mut self.pixels, draw_x, draw_y, zoom, zoom, *color);
                     }
                 }
             }
 
             if let Some((x1, y1, x2, y2)) = self.selection {
-                let x = CANVAS_X + x1 * PIXEL_SIZE;
-                let y = DRAW_Y + y1 * PIXEL_SIZE;
-                let w = (x2 - x1 + 1) * PIXEL_SIZE;
-                let h = (y2 - y1 + 1) * PIXEL_SIZE;
+                let x = ox + x1 * zoom;
+                let y = oy + y1 * zoom;
+                let w = (x2 - x1 + 1) * zoom;
+                let h = (y2 - y1 + 1) * zoom;
                 rect(&mut self.pixels, x, y, w, h, Colors::White);
                 rect(&mut self.pixels, x - 1, y - 1, w + 2, h + 2, Colors::Black);
             }
         } else {
+            if self.tool == Tools::Hitbox {
+                self.drag_hitbox(on_canvas, grid_x, grid_y);
+            }
             if self.mouse.pressed && on_canvas {
                 self.handle_click(grid_y as usize, grid_x as usize);
             }
@@ -307,6 +389,105 @@ impl SpriteEngine {
         }
     }
 
+    fn drag_hitbox(&mut self, on_canvas: bool, grid_x: i32, grid_y: i32) {
+        if self.mouse.just_pressed && on_canvas {
+            self.hitbox_start = Some((grid_x, grid_y));
+        }
+        if !self.mouse.pressed {
+            self.hitbox_start = None;
+        }
+
+        if let Some((sx, sy)) = self.hitbox_start {
+            let hitbox =
+                (sx.min(grid_x), sy.min(grid_y), (sx - grid_x).abs() + 1, (sy - grid_y).abs() + 1);
+            self.edit_info(|info| info.hitbox = Some(hitbox));
+        }
+    }
+
+    //Only while one of their tools is out so they don't get in the way of drawing
+    fn draw_info_overlay(&mut self) {
+        if self.tool != Tools::Pivot && self.tool != Tools::Hitbox {
+            return;
+        }
+
+        let info = self.sprite_info[self.idx];
+        let zoom = self.zoom();
+        let (ox, oy) = self.canvas_origin();
+        if let Some((x, y, w, h)) = info.hitbox {
+            rect(&mut self.pixels, ox + x * zoom, oy + y * zoom, w * zoom, h * zoom, Colors::Red);
+        }
+        if let Some((x, y)) = info.pivot {
+            let (x, y) = (ox + x * zoom, oy + y * zoom);
+            rect(&mut self.pixels, x, y, zoom, zoom, Colors::White);
+            rect(&mut self.pixels, x - 1, y - 1, zoom + 2, zoom + 2, Colors::Black);
+        }
+    }
+
+    //Returns the flag under the mouse so the label can say which one it is
+    fn flag_toggles(&mut self) -> Option<usize> {
+        let x = META_X + (BUTTON_WIDTH - FLAG_SIZE) / 2;
+        let mut hovered = None;
+        for (flag, col) in FLAG_COLORS.iter().enumerate() {
+            let y = DRAW_Y + flag as i32 * FLAG_GAP;
+            if self.mouse.x >= x
+                && self.mouse.x < x + FLAG_SIZE
+                && self.mouse.y >= y
+                && self.mouse.y < y + FLAG_SIZE
+            {
+                hovered = Some(flag);
+                if self.mouse.just_pressed {
+                    self.edit_info(|info| info.set_flag(flag, !info.flag(flag)));
+                }
+            }
+
+            if self.sprite_info[self.idx].flag(flag) {
+                rect_fill(&mut self.pixels, x, y, FLAG_SIZE, FLAG_SIZE, *col);
+            } else {
+                rect(&mut self.pixels, x, y, FLAG_SIZE, FLAG_SIZE, Colors::Gray);
+            }
+        }
+        hovered
+    }
+
+    //Keeps the top left like resizing a font, new space starts see through
+    fn resize_sprite(&mut self, size: usize) {
+        if self.size() == size {
+            return;
+        }
+        self.stamp_selection();
+        self.selection = None;
+        self.selection_start_pos = None;
+        self.move_start_info = None;
+
+        let sprite = &mut self.sprite_sheet[self.idx];
+        let mut resized = vec![vec![Colors::Blank; size]; size];
+        for (y, row) in sprite.iter().enumerate().take(size) {
+            for (x, col) in row.iter().enumerate().take(size) {
+                resized[y][x] = *col;
+            }
+        }
+        *sprite = resized;
+        self.upto_date = false;
+        self.edit_info(|info| info.clip(size as i32));
+    }
+
+    fn size_buttons(&mut self) {
+        for (i, size) in SPRITE_SIZES.iter().enumerate() {
+            let x = CANVAS_X + i as i32 * SIZE_BUTTON_WIDTH;
+            if self.mouse.just_pressed
+                && self.mouse.x >= x
+                && self.mouse.x < x + SIZE_BUTTON_WIDTH
+                && self.mouse.y >= SIZE_Y
+                && self.mouse.y < SIZE_Y + 7
+            {
+                self.resize_sprite(*size);
+            }
+
+            let col = if self.size() == *size { Colors::White } else { Colors::Gray };
+            print_scr_mid(&mut self.pixels, x, SIZE_Y, col, size.to_string());
+        }
+    }
+
     fn tool_button(&mut self, x: i32, y: i32, tool: Tools) {
         draw(&mut self.pixels, x + 1, y + 1, &image_from_tool(tool));
 
@@ -375,14 +556,19 @@ impl SpriteEngine {
                 self.selection = None;
                 self.selection_start_pos = None;
                 self.move_start_info = None;
-                let mut pushing: Vec<(usize, usize, Colors)> = Vec::new();
-                for change in changes {
-                    pushing.push((
-                        change.0,
-                        change.1,
-                        self.sprite_sheet[self.idx][change.0][change.1],
-                    ));
-                    self.sprite_sheet[self.idx][change.0][change.1] = change.2;
+                let mut pushing: Vec<SpriteChange> = Vec::new();
+                for (sprite, y, x, col) in changes {
+                    //Pixels that got cut off by shrinking the sprite since are just gone
+                    let Some(pix) = self
+                        .sprite_sheet
+                        .get_mut(sprite)
+                        .and_then(|rows| rows.get_mut(y))
+                        .and_then(|row| row.get_mut(x))
+                    else {
+                        continue;
+                    };
+                    pushing.push((sprite, y, x, *pix));
+                    *pix = col;
                 }
                 if t == 1 {
                     self.redo_stack.push(pushing)
@@ -440,8 +626,8 @@ impl SpriteEngine {
         {
             match util {
                 Utils::FlipVert => {
-                    if self.moving_selection_content.is_some() {
-                        self.moving_selection_content.as_mut().unwrap().reverse();
+                    if let Some(content) = self.moving_selection_content.as_mut() {
+                        content.reverse();
                     } else if let Some((x1, y1, x2, y2)) = self.selection {
                         let w = (x2 - x1 + 1) as usize;
                         let h = (y2 - y1 + 1) as usize;
@@ -458,10 +644,9 @@ impl SpriteEngine {
                     }
                 }
                 Utils::FlipHor => {
-                    if self.moving_selection_content.is_some() {
-                        let h = self.moving_selection_content.as_ref().unwrap().len();
-                        for i in 0..h {
-                            self.moving_selection_content.as_mut().unwrap()[i].reverse();
+                    if let Some(content) = self.moving_selection_content.as_mut() {
+                        for row in content.iter_mut() {
+                            row.reverse();
                         }
                     } else if let Some((x1, y1, x2, y2)) = self.selection {
                         let w = (x2 - x1 + 1) as usize;
@@ -478,11 +663,16 @@ impl SpriteEngine {
                         }
                     }
                 }
+                Utils::Clear if self.tool == Tools::Pivot => {
+                    self.edit_info(|info| info.pivot = None)
+                }
+                Utils::Clear if self.tool == Tools::Hitbox => {
+                    self.edit_info(|info| info.hitbox = None)
+                }
                 Utils::Clear => {
-                    if self.moving_selection_content.is_some() {
-                        let h = self.moving_selection_content.as_ref().unwrap().len();
-                        for i in 0..h {
-                            self.moving_selection_content.as_mut().unwrap()[i].fill(Colors::Blank);
+                    if let Some(content) = self.moving_selection_content.as_mut() {
+                        for row in content.iter_mut() {
+                            row.fill(Colors::Blank);
                         }
                     } else if let Some((x1, y1, x2, y2)) = self.selection {
                         let w = (x2 - x1 + 1) as usize;
@@ -493,32 +683,114 @@ impl SpriteEngine {
                             }
                         }
                     } else {
-                        for i in 0..SPRITE_SIZE {
-                            for j in 0..SPRITE_SIZE {
+                        for i in 0..self.size() {
+                            for j in 0..self.size() {
                                 self.set_pix(i, j, Colors::Blank);
                             }
                         }
                     }
                 }
+                Utils::Palette => {
+                    self.editing_palette = !self.editing_palette;
+                    self.editing_anims = false;
+                }
+                Utils::Animation => {
+                    self.editing_anims = !self.editing_anims;
+                    self.editing_palette = false;
+                }
                 Utils::Save => {
                     self.upto_date = true;
                     let _ = update_sprites(&self.sprite_sheet);
+                    let _ = update_sprite_info(&self.sprite_info);
+                    let _ = update_animations(&self.anim_strip.animations);
+                    let _ = update_palette(&self.palette);
                 }
             }
         }
+
+        if (util == Utils::Palette && self.editing_palette)
+            || (util == Utils::Animation && self.editing_anims)
+        {
+            rect(&mut self.pixels, x, y, BUTTON_WIDTH - 1, BUTTON_WIDTH - 1, Colors::White);
+        }
+    }
+
+    /* Takes over the canvas while active, edits whichever color is picked up top
+     * Everything on this screen renders through self.palette so the sheet updates live
+     */
+    fn draw_palette_editor(&mut self) {
+        let slot = self.selected_color as usize - 1;
+        let (r, g, b, a) = self.palette[slot];
+        let mut channels = [r, g, b];
+
+        let canvas_width = CANVAS_SIZE;
+        rect_fill(
+            &mut self.pixels,
+            CANVAS_X,
+            DRAW_Y,
+            canvas_width,
+            SWATCH_HEIGHT,
+            self.selected_color,
+        );
+        rect(&mut self.pixels, CANVAS_X, DRAW_Y, canvas_width, SWATCH_HEIGHT, Colors::Gray);
+
+        for (i, label) in ["R", "G", "B"].iter().enumerate() {
+            let y = SLIDER_Y + i as i32 * SLIDER_GAP;
+
+            if self.mouse.pressed
+                && self.mouse.x >= SLIDER_X - 2
+                && self.mouse.x < SLIDER_X + SLIDER_WIDTH + 2
+                && self.mouse.y >= y - 1
+                && self.mouse.y <= y + SLIDER_HEIGHT
+            {
+                let pos = (self.mouse.x - SLIDER_X).clamp(0, SLIDER_WIDTH - 1);
+                channels[i] = (pos * 255 / (SLIDER_WIDTH - 1)) as u8;
+            }
+
+            let filled = channels[i] as i32 * (SLIDER_WIDTH - 1) / 255;
+            print_scr_mid(&mut self.pixels, CANVAS_X, y, Colors::Gray, label.to_string());
+            rect_fill(&mut self.pixels, SLIDER_X, y, SLIDER_WIDTH, SLIDER_HEIGHT, Colors::Gray);
+            rect_fill(&mut self.pixels, SLIDER_X, y, filled + 1, SLIDER_HEIGHT, Colors::White);
+            print_scr_mid(
+                &mut self.pixels,
+                SLIDER_X + SLIDER_WIDTH + 4,
+                y,
+                Colors::Gray,
+                channels[i].to_string(),
+            );
+        }
+
+        let (x, y, w, h) = RESET_BUTTON;
+        rect(&mut self.pixels, x, y, w, h, Colors::Gray);
+        print_scr_mid(&mut self.pixels, x + 3, y + 2, Colors::Gray, "Reset".to_string());
+        if self.mouse.just_pressed
+            && self.mouse.x >= x
+            && self.mouse.x <= x + w
+            && self.mouse.y >= y
+            && self.mouse.y <= y + h
+        {
+            let (r, g, b, _) = DEFAULT_PALETTE[slot];
+            channels = [r, g, b];
+        }
+
+        let updated = (channels[0], channels[1], channels[2], a);
+        if updated != self.palette[slot] {
+            self.palette[slot] = updated;
+            self.upto_date = false;
+        }
     }
 
     fn sprite_small(&mut self, idx: i32, true_idx: i32) {
         let y = SPRITESHEET_Y + (idx / SPRITESHEET_COLS) * SPRITE_PREVIEW_SIZE;
         let x = CANVAS_X + (idx % SPRITESHEET_COLS) * SPRITE_PREVIEW_SIZE;
+        //Every size gets squished or blown up to fill its slot
+        let sprite = &self.sprite_sheet[true_idx as usize];
+        let scale = sprite.len() as i32;
         for i in 0..SPRITE_PREVIEW_SIZE {
             for j in 0..SPRITE_PREVIEW_SIZE {
-                set_pix(
-                    &mut self.pixels,
-                    y + i,
-                    x + j,
-                    self.sprite_sheet[true_idx as usize][i as usize * 2][j as usize * 2],
-                );
+                let col = sprite[(i * scale / SPRITE_PREVIEW_SIZE) as usize]
+                    [(j * scale / SPRITE_PREVIEW_SIZE) as usize];
+                set_pix(&mut self.pixels, y + i, x + j, col);
             }
         }
 
@@ -531,6 +803,12 @@ impl SpriteEngine {
             && self.mouse.y >= y
             && self.mouse.y < y + SPRITE_PREVIEW_SIZE
         {
+            //Picking sprites for a clip shouldn't also switch what the canvas is on
+            if self.editing_anims {
+                self.anim_strip.add_frame(true_idx as usize);
+                self.upto_date = false;
+                return;
+            }
             self.idx = true_idx as usize;
             self.selection = None;
             self.selection_start_pos = None;
@@ -581,7 +859,7 @@ impl SpriteEngine {
             &mut self.pixels,
             CANVAS_X + SPRITE_PREVIEW_SIZE * SPRITESHEET_COLS,
             SPRITESHEET_Y + scroll_start as i32,
-            PIXEL_SIZE,
+            SCROLL_BAR_WIDTH,
             (scroll_end - scroll_start) as i32,
             Colors::White,
         );
@@ -609,15 +887,21 @@ impl SpriteEngine {
             && self.mouse.y > ADD_SPRITE_BUTTON_Y
             && self.mouse.y < ADD_SPRITE_BUTTON_Y + ADD_SPRITE_BUTTON_SIZE
         {
-            let adding = vec![Colors::pixels(SPRITE_SIZE, SPRITE_SIZE); SPRITES_TO_ADD];
+            //New slots match whatever is being edited, so a sheet of 8x8s stays 8x8
+            let size = self.size();
+            let adding = vec![Colors::pixels(size, size); SPRITES_TO_ADD];
             self.sprite_sheet.extend(adding);
+            fit_sprite_info(&mut self.sprite_info, self.sprite_sheet.len());
             let _ = update_sprites(&self.sprite_sheet);
         }
     }
 
     pub fn update(&mut self) {
         self.frame_hash = (self.frame_hash + 1) % FRAME_HASH_MODULO;
-        sync(&mut self.last_time, FRAME_RATE);
+        let dt = sync(&mut self.last_time, FRAME_RATE);
+        //Saved pixels would be stale once everything redraws
+        self.cursor_layer.erase(&mut self.pixels);
+        self.cursor = Cursor::Os;
         clear(&mut self.pixels, Colors::Black);
         //clear(&mut self.pixels, COLORS::GRAY);
 
@@ -641,19 +925,44 @@ impl SpriteEngine {
             self.tool_button(4 + (idx % COLORS_PER_ROW) * BUTTON_WIDTH, TOOLS_Y, *tool);
         }
 
-        for (i, util) in [Utils::FlipHor, Utils::FlipVert, Utils::Clear].iter().enumerate() {
+        for (i, util) in
+            [Utils::FlipHor, Utils::FlipVert, Utils::Clear, Utils::Palette].iter().enumerate()
+        {
             let idx = i as i32;
             self.util_button(UTILS_X + (idx % COLORS_PER_ROW) * BUTTON_WIDTH, TOOLS_Y, *util);
         }
+        self.util_button(ANIM_X, TOOLS_Y, Utils::Animation);
         self.util_button(SAVE_X, TOOLS_Y, Utils::Save);
 
-        let mut sprite_text = "Editing sprite ".to_owned() + &self.idx.to_string();
+        let mut sprite_text = if self.editing_palette {
+            "Editing color ".to_owned() + &self.selected_color.to_string()
+        } else if self.editing_anims {
+            self.anim_strip.label()
+        } else {
+            "Editing sprite ".to_owned() + &self.idx.to_string()
+        };
+        if self.editing_palette {
+            self.draw_palette_editor();
+        } else if self.editing_anims {
+            if self.anim_strip.update(&mut self.pixels, &self.mouse, &self.sprite_sheet, dt) {
+                self.upto_date = false;
+            }
+        } else {
+            self.draw_canvas();
+            self.draw_info_overlay();
+            self.handle_copy_paste();
+
+            if let Some(flag) = self.flag_toggles() {
+                sprite_text = format!("Sprite {} flag {}", self.idx, flag);
+            }
+            self.size_buttons();
+            self.tool_button(META_X, PIVOT_BUTTON_Y, Tools::Pivot);
+            self.tool_button(META_X, HITBOX_BUTTON_Y, Tools::Hitbox);
+        }
         if !self.upto_date {
             sprite_text += "*"
         };
         print_scr_mid(&mut self.pixels, CANVAS_X, DRAW_Y - 8, Colors::Gray, sprite_text);
-        self.draw_canvas();
-        self.handle_copy_paste();
 
         self.handle_undo_redo();
         self.draw_sprite_sheet();
@@ -663,5 +972,7 @@ impl SpriteEngine {
             self.redo_stack.clear();
             self.new_changes = Vec::new();
         }
+
+        self.cursor_layer.draw(&mut self.pixels, self.cursor, &[], self.mouse.x, self.mouse.y);
     }
 }
@@ -0,0 +1,135 @@
+use std::{error::Error, fmt, fs, str::FromStr};
+
+use winit::event::VirtualKeyCode;
+
+use crate::input::keyboard::{key_from_str, key_name};
+
+//Lives next to the cart but isn't part of it, mappings belong to whoever is playing
+pub const CONTROLS_PATH: &str = "controls.cfg";
+pub const PLAYERS: usize = 2;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Button {
+    Left,
+    Right,
+    Up,
+    Down,
+    A,
+    B,
+    Start,
+}
+
+//Index in here is the number lua can use instead of the name
+pub const BUTTONS: [Button; 7] =
+    [Button::Left, Button::Right, Button::Up, Button::Down, Button::A, Button::B, Button::Start];
+
+impl Button {
+    pub fn from_index(idx: usize) -> Option<Button> {
+        BUTTONS.get(idx).copied()
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Button::Left => "Left",
+            Button::Right => "Right",
+            Button::Up => "Up",
+            Button::Down => "Down",
+            Button::A => "A",
+            Button::B => "B",
+            Button::Start => "Start",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for Button {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BUTTONS.iter().find(|button| button.to_string() == s).copied().ok_or(())
+    }
+}
+
+/* Which keys press which button for each player, any of them counts
+ * Saved as plain text so people can edit it by hand, one "player button keys..." per line
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Controls {
+    pub bindings: [[Vec<VirtualKeyCode>; BUTTONS.len()]; PLAYERS],
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        Controls {
+            bindings: [
+                [
+                    vec![Left],
+                    vec![Right],
+                    vec![Up],
+                    vec![Down],
+                    vec![Z, C, N],
+                    vec![X, V, M],
+                    vec![Return],
+                ],
+                [vec![S], vec![F], vec![E], vec![D], vec![Q], vec![W], vec![Tab]],
+            ],
+        }
+    }
+}
+
+impl Controls {
+    pub fn keys(&self, player: usize, button: Button) -> &[VirtualKeyCode] {
+        &self.bindings[player][button as usize]
+    }
+
+    pub fn keys_mut(&mut self, player: usize, button: Button) -> &mut Vec<VirtualKeyCode> {
+        &mut self.bindings[player][button as usize]
+    }
+
+    pub fn load() -> Self {
+        fs::read_to_string(CONTROLS_PATH).map(|text| Controls::from_text(&text)).unwrap_or_default()
+    }
+
+    //Anything missing or unreadable keeps its default so a bad line can't lock anyone out
+    pub fn from_text(text: &str) -> Self {
+        let mut controls = Controls::default();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let mut parts = line.split_whitespace();
+            let player = parts.next().and_then(|p| p.parse::<usize>().ok());
+            let button = parts.next().and_then(|b| b.parse::<Button>().ok());
+            let (Some(player), Some(button)) = (player, button) else {
+                continue;
+            };
+            if player >= PLAYERS {
+                continue;
+            }
+
+            let keys: Vec<VirtualKeyCode> =
+                parts.filter_map(key_from_str).flat_map(|keys| keys.iter().copied()).collect();
+            if !keys.is_empty() {
+                *controls.keys_mut(player, button) = keys;
+            }
+        }
+        controls
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = "# player button keys, key names are the same as key_pressed\n".to_string();
+        for player in 0..PLAYERS {
+            for button in BUTTONS {
+                let keys: Vec<&str> =
+                    self.keys(player, button).iter().filter_map(|key| key_name(*key)).collect();
+                text += &format!("{} {} {}\n", player, button, keys.join(" "));
+            }
+        }
+        text
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::write(CONTROLS_PATH, self.to_text())?;
+        Ok(())
+    }
+}
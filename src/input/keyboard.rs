@@ -1,60 +1,152 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use winit::event::VirtualKeyCode;
 
+//Frames a key has to be held before it starts repeating, then frames between repeats
+pub const REPEAT_DELAY: u32 = 15;
+pub const REPEAT_INTERVAL: u32 = 4;
+
 #[derive(Default)]
 pub struct Keyboard {
     pub keys_pressed: HashSet<VirtualKeyCode>,
     pub keys_just_pressed: HashSet<VirtualKeyCode>,
+    pub keys_just_released: HashSet<VirtualKeyCode>,
+    //Whole frames every held key has been down for, counted in frames so replays line up
+    pub held_frames: HashMap<VirtualKeyCode, u32>,
+    //Characters typed this frame in order, already shifted and laid out by the OS
+    pub text: String,
 }
 
-pub fn key_from_str(str: &str) -> Option<VirtualKeyCode> {
-    //Only keys that we wanna support within the engine
-    //Dont add others just to make sure lua doesn't have full access
-    match str {
-        "1" => Some(VirtualKeyCode::Key1),
-        "2" => Some(VirtualKeyCode::Key2),
-        "3" => Some(VirtualKeyCode::Key3),
-        "4" => Some(VirtualKeyCode::Key4),
-        "5" => Some(VirtualKeyCode::Key5),
-        "6" => Some(VirtualKeyCode::Key6),
-        "7" => Some(VirtualKeyCode::Key7),
-        "8" => Some(VirtualKeyCode::Key8),
-        "9" => Some(VirtualKeyCode::Key9),
-        "0" => Some(VirtualKeyCode::Key0),
-        "A" => Some(VirtualKeyCode::A),
-        "B" => Some(VirtualKeyCode::B),
-        "C" => Some(VirtualKeyCode::C),
-        "D" => Some(VirtualKeyCode::D),
-        "E" => Some(VirtualKeyCode::E),
-        "F" => Some(VirtualKeyCode::F),
-        "G" => Some(VirtualKeyCode::G),
-        "H" => Some(VirtualKeyCode::H),
-        "I" => Some(VirtualKeyCode::I),
-        "J" => Some(VirtualKeyCode::J),
-        "K" => Some(VirtualKeyCode::K),
-        "L" => Some(VirtualKeyCode::L),
-        "M" => Some(VirtualKeyCode::M),
-        "N" => Some(VirtualKeyCode::N),
-        "O" => Some(VirtualKeyCode::O),
-        "P" => Some(VirtualKeyCode::P),
-        "Q" => Some(VirtualKeyCode::Q),
-        "R" => Some(VirtualKeyCode::R),
-        "S" => Some(VirtualKeyCode::S),
-        "T" => Some(VirtualKeyCode::T),
-        "U" => Some(VirtualKeyCode::U),
-        "V" => Some(VirtualKeyCode::V),
-        "W" => Some(VirtualKeyCode::W),
-        "X" => Some(VirtualKeyCode::X),
-        "Y" => Some(VirtualKeyCode::Y),
-        "Z" => Some(VirtualKeyCode::Z),
-        "Left" => Some(VirtualKeyCode::Left),
-        "Up" => Some(VirtualKeyCode::Up),
-        "Right" => Some(VirtualKeyCode::Right),
-        "Down" => Some(VirtualKeyCode::Down),
-        "Back" => Some(VirtualKeyCode::Back),
-        "Enter" => Some(VirtualKeyCode::Return),
-        "Space" => Some(VirtualKeyCode::Space),
-        _ => None,
+impl Keyboard {
+    pub fn pressed(&self, keys: &[VirtualKeyCode]) -> bool {
+        keys.iter().any(|key| self.keys_pressed.contains(key))
+    }
+
+    pub fn just_pressed(&self, keys: &[VirtualKeyCode]) -> bool {
+        keys.iter().any(|key| self.keys_just_pressed.contains(key))
+    }
+
+    pub fn just_released(&self, keys: &[VirtualKeyCode]) -> bool {
+        keys.iter().any(|key| self.keys_just_released.contains(key))
+    }
+
+    //True the frame it goes down, then every interval frames once it's been held for delay
+    pub fn repeated(&self, keys: &[VirtualKeyCode], delay: u32, interval: u32) -> bool {
+        self.just_pressed(keys)
+            || keys.iter().any(|key| match self.held_frames.get(key) {
+                Some(&held) if held >= delay && self.keys_pressed.contains(key) => {
+                    (held - delay) % interval.max(1) == 0
+                }
+                _ => false,
+            })
     }
+
+    //Called once the frame has been handled, clears everything that only lasts a frame
+    pub fn end_frame(&mut self) {
+        self.keys_just_pressed.clear();
+        self.keys_just_released.clear();
+        self.text.clear();
+        self.held_frames.retain(|key, _| self.keys_pressed.contains(key));
+        for key in &self.keys_pressed {
+            *self.held_frames.entry(*key).or_insert(0) += 1;
+        }
+    }
+}
+
+//Every key lua can ask about by name, Escape stays out since it quits
+//Modifiers without a side cover both sides
+pub const KEYS: &[(&str, &[VirtualKeyCode])] = &[
+    ("1", &[VirtualKeyCode::Key1]),
+    ("2", &[VirtualKeyCode::Key2]),
+    ("3", &[VirtualKeyCode::Key3]),
+    ("4", &[VirtualKeyCode::Key4]),
+    ("5", &[VirtualKeyCode::Key5]),
+    ("6", &[VirtualKeyCode::Key6]),
+    ("7", &[VirtualKeyCode::Key7]),
+    ("8", &[VirtualKeyCode::Key8]),
+    ("9", &[VirtualKeyCode::Key9]),
+    ("0", &[VirtualKeyCode::Key0]),
+    ("A", &[VirtualKeyCode::A]),
+    ("B", &[VirtualKeyCode::B]),
+    ("C", &[VirtualKeyCode::C]),
+    ("D", &[VirtualKeyCode::D]),
+    ("E", &[VirtualKeyCode::E]),
+    ("F", &[VirtualKeyCode::F]),
+    ("G", &[VirtualKeyCode::G]),
+    ("H", &[VirtualKeyCode::H]),
+    ("I", &[VirtualKeyCode::I]),
+    ("J", &[VirtualKeyCode::J]),
+    ("K", &[VirtualKeyCode::K]),
+    ("L", &[VirtualKeyCode::L]),
+    ("M", &[VirtualKeyCode::M]),
+    ("N", &[VirtualKeyCode::N]),
+    ("O", &[VirtualKeyCode::O]),
+    ("P", &[VirtualKeyCode::P]),
+    ("Q", &[VirtualKeyCode::Q]),
+    ("R", &[VirtualKeyCode::R]),
+    ("S", &[VirtualKeyCode::S]),
+    ("T", &[VirtualKeyCode::T]),
+    ("U", &[VirtualKeyCode::U]),
+    ("V", &[VirtualKeyCode::V]),
+    ("W", &[VirtualKeyCode::W]),
+    ("X", &[VirtualKeyCode::X]),
+    ("Y", &[VirtualKeyCode::Y]),
+    ("Z", &[VirtualKeyCode::Z]),
+    ("Left", &[VirtualKeyCode::Left]),
+    ("Up", &[VirtualKeyCode::Up]),
+    ("Right", &[VirtualKeyCode::Right]),
+    ("Down", &[VirtualKeyCode::Down]),
+    ("Back", &[VirtualKeyCode::Back]),
+    ("Enter", &[VirtualKeyCode::Return, VirtualKeyCode::NumpadEnter]),
+    ("Space", &[VirtualKeyCode::Space]),
+    ("Tab", &[VirtualKeyCode::Tab]),
+    ("Delete", &[VirtualKeyCode::Delete]),
+    ("Insert", &[VirtualKeyCode::Insert]),
+    ("Home", &[VirtualKeyCode::Home]),
+    ("End", &[VirtualKeyCode::End]),
+    ("PageUp", &[VirtualKeyCode::PageUp]),
+    ("PageDown", &[VirtualKeyCode::PageDown]),
+    ("Pause", &[VirtualKeyCode::Pause]),
+    ("Shift", &[VirtualKeyCode::LShift, VirtualKeyCode::RShift]),
+    ("LShift", &[VirtualKeyCode::LShift]),
+    ("RShift", &[VirtualKeyCode::RShift]),
+    ("Ctrl", &[VirtualKeyCode::LControl, VirtualKeyCode::RControl]),
+    ("LCtrl", &[VirtualKeyCode::LControl]),
+    ("RCtrl", &[VirtualKeyCode::RControl]),
+    ("Alt", &[VirtualKeyCode::LAlt, VirtualKeyCode::RAlt]),
+    ("LAlt", &[VirtualKeyCode::LAlt]),
+    ("RAlt", &[VirtualKeyCode::RAlt]),
+    ("F1", &[VirtualKeyCode::F1]),
+    ("F2", &[VirtualKeyCode::F2]),
+    ("F3", &[VirtualKeyCode::F3]),
+    ("F4", &[VirtualKeyCode::F4]),
+    ("F5", &[VirtualKeyCode::F5]),
+    ("F6", &[VirtualKeyCode::F6]),
+    ("F7", &[VirtualKeyCode::F7]),
+    ("F8", &[VirtualKeyCode::F8]),
+    ("F9", &[VirtualKeyCode::F9]),
+    ("F10", &[VirtualKeyCode::F10]),
+    ("F11", &[VirtualKeyCode::F11]),
+    ("F12", &[VirtualKeyCode::F12]),
+    ("-", &[VirtualKeyCode::Minus, VirtualKeyCode::NumpadSubtract]),
+    ("=", &[VirtualKeyCode::Equals, VirtualKeyCode::NumpadEquals]),
+    ("[", &[VirtualKeyCode::LBracket]),
+    ("]", &[VirtualKeyCode::RBracket]),
+    ("\\", &[VirtualKeyCode::Backslash]),
+    (";", &[VirtualKeyCode::Semicolon]),
+    ("'", &[VirtualKeyCode::Apostrophe]),
+    (",", &[VirtualKeyCode::Comma, VirtualKeyCode::NumpadComma]),
+    (".", &[VirtualKeyCode::Period, VirtualKeyCode::NumpadDecimal]),
+    ("/", &[VirtualKeyCode::Slash, VirtualKeyCode::NumpadDivide]),
+    ("`", &[VirtualKeyCode::Grave]),
+];
+
+pub fn key_from_str(str: &str) -> Option<&'static [VirtualKeyCode]> {
+    KEYS.iter().find(|(name, _)| *name == str).map(|(_, keys)| *keys)
+}
+
+//Name key_from_str knows it by, exact matches first so LShift doesn't come back as Shift
+pub fn key_name(key: VirtualKeyCode) -> Option<&'static str> {
+    let exact = KEYS.iter().find(|(_, keys)| *keys == [key]);
+    exact.or_else(|| KEYS.iter().find(|(_, keys)| keys.contains(&key))).map(|(name, _)| *name)
 }
@@ -1,2 +1,4 @@
+pub mod controller;
 pub mod keyboard;
 pub mod mouse;
+pub mod replay;
@@ -1,16 +1,92 @@
 use mlua::UserData;
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ButtonState {
+    pub pressed: bool,
+    pub just_pressed: bool,
+    pub just_released: bool,
+}
+
+impl ButtonState {
+    pub fn press(&mut self) {
+        self.pressed = true;
+        self.just_pressed = true;
+    }
+
+    //just_pressed stays so a click that starts and ends in the same frame still counts
+    pub fn release(&mut self) {
+        if self.pressed {
+            self.just_released = true;
+        }
+        self.pressed = false;
+    }
+}
+
+//Left button is flat on the struct since every editor only cares about that one
 #[derive(Debug, Clone, Copy)]
 pub struct MousePress {
     pub just_pressed: bool,
     pub pressed: bool,
+    pub just_released: bool,
+    pub right: ButtonState,
+    pub middle: ButtonState,
+    //Lines scrolled this frame, up is positive
+    pub wheel: f32,
     pub x: i32,
     pub y: i32,
+    //Same spot as x and y with the part of a screen pixel they cut off
+    pub fx: f32,
+    pub fy: f32,
 }
 
 impl Default for MousePress {
     fn default() -> Self {
-        MousePress { just_pressed: false, pressed: false, x: -1, y: -1 }
+        MousePress {
+            just_pressed: false,
+            pressed: false,
+            just_released: false,
+            right: ButtonState::default(),
+            middle: ButtonState::default(),
+            wheel: 0.0,
+            x: -1,
+            y: -1,
+            fx: -1.0,
+            fy: -1.0,
+        }
+    }
+}
+
+impl MousePress {
+    pub fn left(&self) -> ButtonState {
+        ButtonState {
+            pressed: self.pressed,
+            just_pressed: self.just_pressed,
+            just_released: self.just_released,
+        }
+    }
+
+    pub fn set_left(&mut self, left: ButtonState) {
+        self.pressed = left.pressed;
+        self.just_pressed = left.just_pressed;
+        self.just_released = left.just_released;
+    }
+
+    //Off the engine nothing counts as held, the position stays -1 until it comes back
+    pub fn release_all(&mut self) {
+        self.set_left(ButtonState::default());
+        self.right = ButtonState::default();
+        self.middle = ButtonState::default();
+    }
+
+    //Called once the frame has been handled, clears everything that only lasts a frame
+    pub fn end_frame(&mut self) {
+        self.just_pressed = false;
+        self.just_released = false;
+        for button in [&mut self.right, &mut self.middle] {
+            button.just_pressed = false;
+            button.just_released = false;
+        }
+        self.wheel = 0.0;
     }
 }
 
@@ -19,7 +95,18 @@ impl UserData for MousePress {
     fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
         fields.add_field_method_get("just_pressed", |_, this| Ok(this.just_pressed));
         fields.add_field_method_get("pressed", |_, this| Ok(this.pressed));
+        fields.add_field_method_get("just_released", |_, this| Ok(this.just_released));
+        fields.add_field_method_get("right_pressed", |_, this| Ok(this.right.pressed));
+        fields.add_field_method_get("right_just_pressed", |_, this| Ok(this.right.just_pressed));
+        fields.add_field_method_get("right_just_released", |_, this| Ok(this.right.just_released));
+        fields.add_field_method_get("middle_pressed", |_, this| Ok(this.middle.pressed));
+        fields.add_field_method_get("middle_just_pressed", |_, this| Ok(this.middle.just_pressed));
+        fields
+            .add_field_method_get("middle_just_released", |_, this| Ok(this.middle.just_released));
+        fields.add_field_method_get("wheel", |_, this| Ok(this.wheel));
         fields.add_field_method_get("x", |_, this| Ok(this.x));
         fields.add_field_method_get("y", |_, this| Ok(this.y));
+        fields.add_field_method_get("fx", |_, this| Ok(this.fx));
+        fields.add_field_method_get("fy", |_, this| Ok(this.fy));
     }
 }
@@ -0,0 +1,317 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bincode::{config::standard, error::DecodeError, Decode, Encode};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    input::{
+        controller::Controls,
+        keyboard::{Keyboard, KEYS},
+        mouse::{ButtonState, MousePress},
+    },
+    scripting::cartridge::capture_path,
+};
+
+const MAGIC: [u8; 4] = *b"R32I";
+const VERSION: u8 = 1;
+
+//How the game gets its input, picked from the command line
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum InputMode {
+    #[default]
+    Live,
+    Record,
+    Replay(PathBuf),
+}
+
+impl InputMode {
+    //--record or --replay=<file>, anything else is left for whoever else reads the args
+    pub fn from_args(args: &[String]) -> Self {
+        for arg in args {
+            if arg == "--record" {
+                return InputMode::Record;
+            }
+            if let Some(path) = arg.strip_prefix("--replay=") {
+                return InputMode::Replay(PathBuf::from(path));
+            }
+        }
+        InputMode::Live
+    }
+
+    //Replays only make sense for the cart they were recorded on
+    pub fn for_other_cart(self) -> Self {
+        match self {
+            InputMode::Replay(_) => InputMode::Live,
+            mode => mode,
+        }
+    }
+}
+
+/* Only keys lua has a name for get stored, nothing else can reach a game anyway
+ * A key is stored as where it first shows up in KEYS, so it stays one small number
+ */
+fn recordable_keys() -> impl Iterator<Item = VirtualKeyCode> {
+    KEYS.iter().flat_map(|(_, keys)| keys.iter().copied())
+}
+
+fn key_index(key: VirtualKeyCode) -> Option<u16> {
+    recordable_keys().position(|k| k == key).map(|idx| idx as u16)
+}
+
+fn key_at(idx: u16) -> Option<VirtualKeyCode> {
+    recordable_keys().nth(idx as usize)
+}
+
+//Sorted so the same input always writes the same bytes
+fn key_indices<'a>(keys: impl Iterator<Item = &'a VirtualKeyCode>) -> Vec<u16> {
+    let mut keys: Vec<u16> = keys.filter_map(|key| key_index(*key)).collect();
+    keys.sort_unstable();
+    keys
+}
+
+fn button_bits(button: ButtonState) -> u16 {
+    button.pressed as u16 | (button.just_pressed as u16) << 1 | (button.just_released as u16) << 2
+}
+
+fn button_from_bits(bits: u16) -> ButtonState {
+    ButtonState {
+        pressed: bits & 1 != 0,
+        just_pressed: bits & 2 != 0,
+        just_released: bits & 4 != 0,
+    }
+}
+
+#[derive(Encode, Decode)]
+struct Header {
+    magic: [u8; 4],
+    version: u8,
+    seed: u64,
+}
+
+//Everything update sees for one frame, controls only when they changed since the last one
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+struct FrameInput {
+    dt: u64,
+    pressed: Vec<u16>,
+    just_pressed: Vec<u16>,
+    just_released: Vec<u16>,
+    held: Vec<(u16, u32)>,
+    text: String,
+    //Left, right and middle, 3 bits each
+    buttons: u16,
+    wheel: f32,
+    x: i32,
+    y: i32,
+    fx: f32,
+    fy: f32,
+    controls: Option<String>,
+}
+
+impl FrameInput {
+    fn new(dt: u128, keyboard: &Keyboard, mouse: &MousePress) -> Self {
+        let mut held: Vec<(u16, u32)> = keyboard
+            .held_frames
+            .iter()
+            .filter_map(|(key, frames)| key_index(*key).map(|idx| (idx, *frames)))
+            .collect();
+        held.sort_unstable();
+
+        FrameInput {
+            dt: dt as u64,
+            pressed: key_indices(keyboard.keys_pressed.iter()),
+            just_pressed: key_indices(keyboard.keys_just_pressed.iter()),
+            just_released: key_indices(keyboard.keys_just_released.iter()),
+            held,
+            text: keyboard.text.clone(),
+            buttons: button_bits(mouse.left())
+                | button_bits(mouse.right) << 3
+                | button_bits(mouse.middle) << 6,
+            wheel: mouse.wheel,
+            x: mouse.x,
+            y: mouse.y,
+            fx: mouse.fx,
+            fy: mouse.fy,
+            controls: None,
+        }
+    }
+
+    //Overwrites whatever live input came in since, so the game only ever sees the recording
+    fn apply(&self, keyboard: &mut Keyboard, mouse: &mut MousePress) {
+        let keys = |indices: &[u16]| indices.iter().filter_map(|idx| key_at(*idx)).collect();
+        keyboard.keys_pressed = keys(&self.pressed);
+        keyboard.keys_just_pressed = keys(&self.just_pressed);
+        keyboard.keys_just_released = keys(&self.just_released);
+        keyboard.held_frames =
+            self.held.iter().filter_map(|(idx, frames)| Some((key_at(*idx)?, *frames))).collect();
+        keyboard.text = self.text.clone();
+
+        mouse.set_left(button_from_bits(self.buttons));
+        mouse.right = button_from_bits(self.buttons >> 3);
+        mouse.middle = button_from_bits(self.buttons >> 6);
+        mouse.wheel = self.wheel;
+        mouse.x = self.x;
+        mouse.y = self.y;
+        mouse.fx = self.fx;
+        mouse.fy = self.fy;
+    }
+}
+
+/* Writes what the game saw every frame straight into a gzipped file next to the cart
+ * Starts with a header holding the seed math.random got, then one FrameInput per update
+ */
+pub struct InputRecorder {
+    encoder: GzEncoder<BufWriter<File>>,
+    path: PathBuf,
+    seed: u64,
+    controls: Option<Controls>,
+    frames: usize,
+}
+
+impl InputRecorder {
+    pub fn start() -> Result<Self, Box<dyn Error>> {
+        let path = capture_path("replay");
+        let mut encoder =
+            GzEncoder::new(BufWriter::new(File::create(&path)?), Compression::default());
+
+        let seed =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        bincode::encode_into_std_write(
+            Header { magic: MAGIC, version: VERSION, seed },
+            &mut encoder,
+            standard(),
+        )?;
+
+        Ok(InputRecorder { encoder, path, seed, controls: None, frames: 0 })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    //Called right before update with the dt it's about to get
+    pub fn capture(
+        &mut self,
+        dt: u128,
+        keyboard: &Keyboard,
+        mouse: &MousePress,
+        controls: &Controls,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut frame = FrameInput::new(dt, keyboard, mouse);
+        if self.controls.as_ref() != Some(controls) {
+            frame.controls = Some(controls.to_text());
+            self.controls = Some(controls.clone());
+        }
+        bincode::encode_into_std_write(frame, &mut self.encoder, standard())?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(PathBuf, usize), Box<dyn Error>> {
+        self.encoder.finish()?.flush()?;
+        Ok((self.path, self.frames))
+    }
+}
+
+//A whole recording read up front, handed out a frame at a time
+pub struct InputReplay {
+    pub path: PathBuf,
+    seed: u64,
+    frames: Vec<FrameInput>,
+    next: usize,
+}
+
+impl InputReplay {
+    pub fn open(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let file =
+            File::open(&path).map_err(|err| format!("Could not open {}: {err}", path.display()))?;
+        let mut decoder = GzDecoder::new(BufReader::new(file));
+
+        let header: Header = bincode::decode_from_std_read(&mut decoder, standard())
+            .map_err(|_| format!("{} isn't a replay", path.display()))?;
+        if header.magic != MAGIC {
+            return Err(format!("{} isn't a replay", path.display()).into());
+        }
+        if header.version != VERSION {
+            return Err(format!("{} is from a different version of RICO-32", path.display()).into());
+        }
+
+        //A recording cut off mid frame still plays up to there
+        let mut frames = Vec::new();
+        loop {
+            match bincode::decode_from_std_read(&mut decoder, standard()) {
+                Ok(frame) => frames.push(frame),
+                Err(DecodeError::UnexpectedEnd { .. }) | Err(DecodeError::Io { .. }) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(InputReplay { path, seed: header.seed, frames, next: 0 })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    //Puts the next frame's input in place and gives back its dt, None once it's all played
+    pub fn next_frame(
+        &mut self,
+        keyboard: &mut Keyboard,
+        mouse: &mut MousePress,
+        controls: &mut Controls,
+    ) -> Option<u128> {
+        let frame = self.frames.get(self.next)?;
+        self.next += 1;
+
+        frame.apply(keyboard, mouse);
+        if let Some(text) = &frame.controls {
+            *controls = Controls::from_text(text);
+        }
+        Some(frame.dt as u128)
+    }
+}
+
+//Where the game's input is coming from right now
+pub enum InputSession {
+    Live,
+    Recording(Box<InputRecorder>),
+    Replaying(InputReplay),
+}
+
+impl InputSession {
+    pub fn start(mode: InputMode) -> Result<Self, Box<dyn Error>> {
+        Ok(match mode {
+            InputMode::Live => InputSession::Live,
+            InputMode::Record => InputSession::Recording(Box::new(InputRecorder::start()?)),
+            InputMode::Replay(path) => InputSession::Replaying(InputReplay::open(path)?),
+        })
+    }
+
+    //What a restart should start with, recordings start a new file and replays start over
+    pub fn mode(&self) -> InputMode {
+        match self {
+            InputSession::Live => InputMode::Live,
+            InputSession::Recording(_) => InputMode::Record,
+            InputSession::Replaying(replay) => InputMode::Replay(replay.path.clone()),
+        }
+    }
+
+    //math.random gets this so both runs roll the same numbers, live games stay random
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            InputSession::Live => None,
+            InputSession::Recording(recorder) => Some(recorder.seed()),
+            InputSession::Replaying(replay) => Some(replay.seed()),
+        }
+    }
+}
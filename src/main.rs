@@ -1,6 +1,9 @@
-use rico_32::engine::rico::RicoEngine;
+use std::env;
+
+use rico_32::{engine::rico::RicoEngine, input::replay::InputMode};
 
 fn main() {
-    let engine = RicoEngine::default();
+    let args: Vec<String> = env::args().collect();
+    let engine = RicoEngine::new(InputMode::from_args(&args));
     engine.start().expect("Couldn't start the RICO-32 Engine!");
 }
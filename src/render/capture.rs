@@ -0,0 +1,155 @@
+use std::{borrow::Cow, error::Error, fs::File, io::BufWriter, path::PathBuf};
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::{
+    render::framebuffer::{Framebuffer, PaletteLut},
+    scripting::cartridge::capture_path,
+};
+
+pub const MAX_CAPTURE_SCALE: usize = 16;
+
+/* Browsers bump anything under 2 centiseconds up to 10, so faster frames get merged
+ * into the next one instead, the time they were up for carries over
+ */
+const MIN_FRAME_MS: u128 = 20;
+
+//Rgb triples in color index order, Blank comes out as black like in the window
+fn rgb_palette(lut: &PaletteLut) -> Vec<u8> {
+    lut.iter().flat_map(|rgba| [rgba[0], rgba[1], rgba[2]]).collect()
+}
+
+//Color indices row by row, each pixel blown up to a scale x scale block
+fn scaled_indices(pixels: &Framebuffer, scale: usize) -> Vec<u8> {
+    let row_len = pixels.width() * scale;
+    let mut out = Vec::with_capacity(row_len * pixels.height() * scale);
+    for row in pixels.rows() {
+        let start = out.len();
+        for pix in row {
+            out.extend(std::iter::repeat(*pix as u8).take(scale));
+        }
+        for _ in 1..scale {
+            out.extend_from_within(start..start + row_len);
+        }
+    }
+    out
+}
+
+fn check_scale(scale: usize) -> Result<(), Box<dyn Error>> {
+    if scale == 0 || scale > MAX_CAPTURE_SCALE {
+        return Err(format!("Capture scale has to be between 1 and {MAX_CAPTURE_SCALE}").into());
+    }
+    Ok(())
+}
+
+//Indexed png straight from the color indices, so no colors get lost along the way
+pub fn save_png(
+    pixels: &Framebuffer,
+    lut: &PaletteLut,
+    scale: usize,
+) -> Result<PathBuf, Box<dyn Error>> {
+    check_scale(scale)?;
+    let path = capture_path("png");
+    let file = BufWriter::new(File::create(&path)?);
+
+    let mut encoder =
+        png::Encoder::new(file, (pixels.width() * scale) as u32, (pixels.height() * scale) as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(rgb_palette(lut));
+    encoder.write_header()?.write_image_data(&scaled_indices(pixels, scale))?;
+
+    Ok(path)
+}
+
+/* Gif that gets a frame every game update, the 17 colors are the gif palette as is
+ * A frame only gets written once the next different one shows up, that's when we know its delay
+ */
+pub struct GifRecorder {
+    encoder: Encoder<BufWriter<File>>,
+    path: PathBuf,
+    scale: usize,
+    size: (u16, u16),
+    global_lut: PaletteLut,
+    //Frame on screen right now, the lut it was drawn with and how long it's been up
+    pending: (Vec<u8>, PaletteLut),
+    pending_ms: u128,
+    //Leftover milliseconds from rounding delays to centiseconds
+    carry_ms: u128,
+}
+
+impl GifRecorder {
+    pub fn start(
+        pixels: &Framebuffer,
+        lut: &PaletteLut,
+        scale: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        check_scale(scale)?;
+        let path = capture_path("gif");
+        let file = BufWriter::new(File::create(&path)?);
+
+        let w = (pixels.width() * scale) as u16;
+        let h = (pixels.height() * scale) as u16;
+        let mut encoder = Encoder::new(file, w, h, &rgb_palette(lut))?;
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        Ok(GifRecorder {
+            encoder,
+            path,
+            scale,
+            size: (w, h),
+            global_lut: *lut,
+            pending: (scaled_indices(pixels, scale), *lut),
+            pending_ms: 0,
+            carry_ms: 0,
+        })
+    }
+
+    //dt is how long the last frame was up for, straight from time::sync
+    pub fn capture(
+        &mut self,
+        pixels: &Framebuffer,
+        lut: &PaletteLut,
+        dt: u128,
+    ) -> Result<(), Box<dyn Error>> {
+        self.pending_ms += dt;
+        let frame = (scaled_indices(pixels, self.scale), *lut);
+        if frame == self.pending {
+            return Ok(());
+        }
+
+        if self.pending_ms >= MIN_FRAME_MS {
+            self.write_pending()?;
+        }
+        self.pending = frame;
+        Ok(())
+    }
+
+    fn write_pending(&mut self) -> Result<(), Box<dyn Error>> {
+        let total = self.pending_ms + self.carry_ms;
+        self.carry_ms = total % 10;
+        self.pending_ms = 0;
+
+        let (indices, lut) = &self.pending;
+        let mut frame = Frame {
+            width: self.size.0,
+            height: self.size.1,
+            buffer: Cow::Borrowed(indices),
+            delay: (total / 10).clamp(1, u16::MAX as u128) as u16,
+            ..Frame::default()
+        };
+        //Only frames drawn with a different display palette need their own
+        if *lut != self.global_lut {
+            frame.palette = Some(rgb_palette(lut));
+        }
+        self.encoder.write_frame(&frame)?;
+        Ok(())
+    }
+
+    //Writes whatever is still on screen and closes the file
+    pub fn finish(mut self) -> Result<PathBuf, Box<dyn Error>> {
+        self.write_pending()?;
+        self.encoder.into_inner()?;
+        Ok(self.path)
+    }
+}
@@ -32,7 +32,34 @@ impl Colors {
         vec![vec![Colors::Black; width]; height]
     }
 
-    pub fn rgba(self) -> (u8, u8, u8, u8) {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Colors::Black => "BLACK",
+            Colors::White => "WHITE",
+            Colors::Gray => "GRAY",
+            Colors::Silver => "SILVER",
+            Colors::Red => "RED",
+            Colors::Maroon => "MAROON",
+            Colors::Orange => "ORANGE",
+            Colors::Yellow => "YELLOW",
+            Colors::Gold => "GOLD",
+            Colors::Green => "GREEN",
+            Colors::Olive => "OLIVE",
+            Colors::Brown => "BROWN",
+            Colors::Blue => "BLUE",
+            Colors::Teal => "TEAL",
+            Colors::Purple => "PURPLE",
+            Colors::Pink => "PINK",
+            Colors::Blank => "BLANK",
+        }
+    }
+
+    //Same order as the enum, so BLANK is 0
+    pub fn from_index(idx: usize) -> Option<Colors> {
+        ALL_COLORS.get(idx).copied()
+    }
+
+    pub const fn rgba(self) -> (u8, u8, u8, u8) {
         match self {
             Colors::Blank => (0, 0, 0, 0),
             Colors::Black => (0, 0, 0, 255),
@@ -53,63 +80,50 @@ impl Colors {
             Colors::Pink => (255, 105, 180, 255),
         }
     }
+
+    //Same as rgba but with a cartridge palette, Blank is never in it and always see through
+    pub fn rgba_in(self, palette: &Palette) -> (u8, u8, u8, u8) {
+        match self {
+            Colors::Blank => (0, 0, 0, 0),
+            _ => palette[self as usize - 1],
+        }
+    }
 }
 
+//One RGBA per color slot, skipping Blank, so color names keep meaning the same slot
+pub type Palette = [(u8, u8, u8, u8); 16];
+
+pub const DEFAULT_PALETTE: Palette = {
+    let mut palette = [(0, 0, 0, 0); 16];
+    let mut i = 0;
+    while i < palette.len() {
+        palette[i] = ALL_COLORS[i + 1].rgba();
+        i += 1;
+    }
+    palette
+};
+
 //So that we can parse to strings for get_pix primarily
 impl fmt::Display for Colors {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s = match self {
-            Colors::Black => "BLACK",
-            Colors::White => "WHITE",
-            Colors::Gray => "GRAY",
-            Colors::Silver => "SILVER",
-            Colors::Red => "RED",
-            Colors::Maroon => "MAROON",
-            Colors::Orange => "ORANGE",
-            Colors::Yellow => "YELLOW",
-            Colors::Gold => "GOLD",
-            Colors::Green => "GREEN",
-            Colors::Olive => "OLIVE",
-            Colors::Brown => "BROWN",
-            Colors::Blue => "BLUE",
-            Colors::Teal => "TEAL",
-            Colors::Purple => "PURPLE",
-            Colors::Pink => "PINK",
-            Colors::Blank => "BLANK",
-        };
-        write!(f, "{s}")
+        write!(f, "{}", self.name())
     }
 }
 
-//So we can parse from strings for all lua calls
+//So we can parse from strings for all lua calls, no allocating since this runs per draw
 impl FromStr for Colors {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_uppercase().as_str() {
-            "BLACK" => Ok(Colors::Black),
-            "WHITE" => Ok(Colors::White),
-            "GRAY" => Ok(Colors::Gray),
-            "SILVER" => Ok(Colors::Silver),
-            "RED" => Ok(Colors::Red),
-            "MAROON" => Ok(Colors::Maroon),
-            "ORANGE" => Ok(Colors::Orange),
-            "YELLOW" => Ok(Colors::Yellow),
-            "GOLD" => Ok(Colors::Gold),
-            "GREEN" => Ok(Colors::Green),
-            "OLIVE" => Ok(Colors::Olive),
-            "BROWN" => Ok(Colors::Brown),
-            "BLUE" => Ok(Colors::Blue),
-            "TEAL" => Ok(Colors::Teal),
-            "PURPLE" => Ok(Colors::Purple),
-            "PINK" => Ok(Colors::Pink),
-            "BLANK" => Ok(Colors::Blank),
-            _ => Err(()),
-        }
+        ALL_COLORS.iter().find(|c| c.name().eq_ignore_ascii_case(s)).copied().ok_or(())
     }
 }
 
-pub const ALL_COLORS: [Colors; 17] = [
+//Lookup from one color to another, indexed by the color's value
+pub type ColorMap = [Colors; 17];
+
+//In discriminant order so it doubles as the identity ColorMap
+pub const ALL_COLORS: ColorMap = [
     Colors::Blank,
     Colors::Black,
     Colors::White,
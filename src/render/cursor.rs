@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use crate::{
+    engine::rico::PixelsType,
+    render::{
+        colors::Colors,
+        framebuffer::Framebuffer,
+        pixels::{draw, Target},
+    },
+};
+
+const K: Colors = Colors::Black;
+const W: Colors = Colors::White;
+const B: Colors = Colors::Blank;
+const POINTER_IMAGE: [[Colors; 7]; 9] = [
+    [K, B, B, B, B, B, B],
+    [K, K, B, B, B, B, B],
+    [K, W, K, B, B, B, B],
+    [K, W, W, K, B, B, B],
+    [K, W, W, W, K, B, B],
+    [K, W, W, W, W, K, B],
+    [K, W, W, K, K, K, K],
+    [K, W, K, B, B, B, B],
+    [K, K, B, B, B, B, B],
+];
+//Middle is left open so the pixel under it stays visible
+const CROSSHAIR_IMAGE: [[Colors; 7]; 7] = [
+    [B, B, B, W, B, B, B],
+    [B, B, B, K, B, B, B],
+    [B, B, B, B, B, B, B],
+    [W, K, B, B, B, K, W],
+    [B, B, B, B, B, B, B],
+    [B, B, B, K, B, B, B],
+    [B, B, B, W, B, B, B],
+];
+
+//What gets drawn where the mouse is, anything but Os hides the real one over the pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cursor {
+    #[default]
+    Os,
+    Hidden,
+    Pointer,
+    Crosshair,
+    //Sprite index and the hot spot, the pixel of it that sits on the mouse
+    Sprite(usize, i32, i32),
+}
+
+impl FromStr for Cursor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pointer" => Ok(Cursor::Pointer),
+            "crosshair" => Ok(Cursor::Crosshair),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Cursor {
+    pub fn hides_os(&self) -> bool {
+        *self != Cursor::Os
+    }
+}
+
+/* Draws a cursor into a screen and remembers what was under it
+ * so it can be taken off again before anything else draws there
+ */
+#[derive(Debug, Default)]
+pub struct CursorLayer {
+    under: Vec<(i32, i32, Colors)>,
+}
+
+impl CursorLayer {
+    //x and y of -1 mean the mouse is off the pane, sprites that don't exist draw nothing
+    pub fn draw(
+        &mut self,
+        pixels: &mut Framebuffer,
+        cursor: Cursor,
+        sprites: &[PixelsType],
+        x: i32,
+        y: i32,
+    ) {
+        //Only ever one on screen, drawing twice would save the first one as what's under it
+        self.erase(pixels);
+        if x == -1 || y == -1 {
+            return;
+        }
+        match cursor {
+            Cursor::Os | Cursor::Hidden => {}
+            Cursor::Pointer => self.stamp(pixels, x, y, &POINTER_IMAGE),
+            Cursor::Crosshair => self.stamp(pixels, x - 3, y - 3, &CROSSHAIR_IMAGE),
+            Cursor::Sprite(idx, hx, hy) => {
+                if let Some(sprite) = sprites.get(idx) {
+                    self.stamp(pixels, x - hx, y - hy, sprite);
+                }
+            }
+        }
+    }
+
+    fn stamp<P, R>(&mut self, pixels: &mut Framebuffer, x: i32, y: i32, img: &P)
+    where
+        P: AsRef<[R]>,
+        R: AsRef<[Colors]>,
+    {
+        for (j, row) in img.as_ref().iter().enumerate() {
+            for (i, col) in row.as_ref().iter().enumerate() {
+                let (px, py) = (x + i as i32, y + j as i32);
+                if *col == Colors::Blank {
+                    continue;
+                }
+                if let Some(old) = pixels.get_pix(py, px) {
+                    self.under.push((px, py, old));
+                }
+            }
+        }
+        draw(pixels, x, y, img);
+    }
+
+    //Puts back whatever the last draw covered
+    pub fn erase(&mut self, pixels: &mut Framebuffer) {
+        for (x, y, col) in self.under.drain(..).rev() {
+            pixels.put_pix(y, x, col);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::render::{
+    colors::{ColorMap, Colors, Palette},
+    pixels::Target,
+};
+
+//Final rgba for every color slot, display palette already applied
+pub type PaletteLut = [[u8; 4]; 17];
+
+pub fn palette_lut(palette: &Palette, display_palette: &ColorMap) -> PaletteLut {
+    let mut lut = [[0; 4]; 17];
+    for (i, rgba) in lut.iter_mut().enumerate() {
+        let (r, g, b, a) = display_palette[i].rgba_in(palette);
+        *rgba = [r, g, b, a];
+    }
+    lut
+}
+
+/* Screen of an engine, one flat row by row vec instead of a vec per row
+ * Keeps track of whether anything changed since it last went to the window
+ * so panes that sit still (nav bar, idle console) don't get copied every frame
+ */
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    data: Vec<Colors>,
+    dirty: bool,
+    //Lookup it was last presented with, None means it has to go out again no matter what
+    presented: Option<PaletteLut>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            data: vec![Colors::Black; width * height],
+            dirty: true,
+            presented: None,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn rows(&self) -> std::slice::ChunksExact<'_, Colors> {
+        self.data.chunks_exact(self.width)
+    }
+
+    //Something else drew over our part of the window, like another tab
+    pub fn invalidate(&mut self) {
+        self.presented = None;
+    }
+
+    pub fn needs_present(&self, lut: &PaletteLut) -> bool {
+        self.dirty || self.presented.as_ref() != Some(lut)
+    }
+
+    pub fn mark_presented(&mut self, lut: PaletteLut) {
+        self.dirty = false;
+        self.presented = Some(lut);
+    }
+}
+
+impl Target for Framebuffer {
+    fn get_pix(&self, y: i32, x: i32) -> Option<Colors> {
+        if y < 0 || x < 0 || y as usize >= self.height || x as usize >= self.width {
+            return None;
+        }
+        Some(self.data[y as usize * self.width + x as usize])
+    }
+
+    fn put_pix(&mut self, y: i32, x: i32, col: Colors) {
+        if y < 0 || x < 0 || y as usize >= self.height || x as usize >= self.width {
+            return;
+        }
+
+        //Only real changes count, editors redraw everything every frame
+        let pix = &mut self.data[y as usize * self.width + x as usize];
+        if *pix != col {
+            *pix = col;
+            self.dirty = true;
+        }
+    }
+
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        (0, 0, self.width as i32, self.height as i32)
+    }
+}
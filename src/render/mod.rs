@@ -1,3 +1,8 @@
 pub mod bitmap;
+pub mod capture;
 pub mod colors;
+pub mod cursor;
+pub mod framebuffer;
 pub mod pixels;
+pub mod screen;
+pub mod text;
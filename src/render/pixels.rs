@@ -1,15 +1,62 @@
 use crate::{
     engine::{
+        map::MapTools,
         rico::PixelsType,
-        sprite::{Tools, Utils, BUTTON_WIDTH},
+        sprite::{Tools, Utils, BUTTON_WIDTH, SPRITESHEET_COLS, SPRITE_SIZE},
     },
     render::{
-        bitmap::{BITMAP, BITMAP4X4, BITMAP4X6},
         colors::Colors,
+        text::{print_text, Font, TextStyle},
     },
+    scripting::tilemap::Tilemap,
 };
 
-pub fn set_pix(pixels: &mut PixelsType, y: i32, x: i32, col: Colors) {
+const DIRS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/* Anything the draw functions can render into, coords are in draw space
+ * Raw pixel buffers write straight through, the lua screen applies its draw state first
+ */
+pub trait Target {
+    fn get_pix(&self, y: i32, x: i32) -> Option<Colors>;
+
+    fn put_pix(&mut self, y: i32, x: i32, col: Colors);
+
+    //Readable area as (x, y, w, h) in draw space
+    fn bounds(&self) -> (i32, i32, i32, i32);
+
+    //Which image colors get skipped when blitting sprites
+    fn is_transparent(&self, col: Colors) -> bool {
+        col == Colors::Blank
+    }
+
+    //Pixels of filled shapes, lets the lua screen apply its fill pattern
+    fn put_fill(&mut self, y: i32, x: i32, col: Colors) {
+        self.put_pix(y, x, col);
+    }
+}
+
+impl Target for PixelsType {
+    fn get_pix(&self, y: i32, x: i32) -> Option<Colors> {
+        if y < 0 || x < 0 {
+            return None;
+        }
+        self.get(y as usize).and_then(|row| row.get(x as usize)).copied()
+    }
+
+    fn put_pix(&mut self, y: i32, x: i32, col: Colors) {
+        if y < 0 || x < 0 || y >= self.len() as i32 || x >= self[0].len() as i32 {
+            return;
+        }
+
+        self[y as usize][x as usize] = col;
+    }
+
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        (0, 0, self[0].len() as i32, self.len() as i32)
+    }
+}
+
+pub fn set_pix<T: Target>(pixels: &mut T, y: i32, x: i32, col: Colors) {
     //If the new pixel has 0 alpha, just keep the old guy
     //We don't wanna implement full alpha stuff cause pixel art
     //This much is fine for images with empty bgs
@@ -17,132 +64,671 @@ pub fn set_pix(pixels: &mut PixelsType, y: i32, x: i32, col: Colors) {
         return;
     }
 
-    if y < 0 || x < 0 || y >= pixels.len() as i32 || x >= pixels[0].len() as i32 {
+    pixels.put_pix(y, x, col);
+}
+
+//Same as set_pix but for image pixels, so transparent colors get skipped
+pub fn blit_pix<T: Target>(pixels: &mut T, y: i32, x: i32, col: Colors) {
+    if pixels.is_transparent(col) {
+        return;
+    }
+
+    pixels.put_pix(y, x, col);
+}
+
+//Same as set_pix but for the inside of filled shapes
+pub fn fill_pix<T: Target>(pixels: &mut T, y: i32, x: i32, col: Colors) {
+    if col == Colors::Blank {
         return;
     }
 
-    pixels[y as usize][x as usize] = col;
+    pixels.put_fill(y, x, col);
 }
 
 //Generics so we can pass in both vecs and arrays as whats necessary
-pub fn draw<P, R>(pixels: &mut PixelsType, x: i32, y: i32, img: &P)
+pub fn draw<T, P, R>(pixels: &mut T, x: i32, y: i32, img: &P)
 where
+    T: Target,
     P: AsRef<[R]>,
     R: AsRef<[Colors]>,
 {
     for (j, row) in img.as_ref().iter().enumerate() {
         for (i, col) in row.as_ref().iter().enumerate() {
-            set_pix(pixels, y + j as i32, x + i as i32, *col);
+            blit_pix(pixels, y + j as i32, x + i as i32, *col);
         }
     }
 }
 
-/* Loop over every character and use the 8x8 bitmap
- * Use bitmasking to check which pixels to be set
+//Options for draw_ex, angle is in degrees clockwise and scale can be fractional
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub angle: f32,
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform { flip_x: false, flip_y: false, angle: 0.0, scale_x: 1.0, scale_y: 1.0 }
+    }
+}
+
+//Float trig never gives exact 0s and 1s which shifts quarter turns by a pixel
+fn snap(v: f32) -> f32 {
+    if (v - v.round()).abs() < 1e-6 {
+        v.round()
+    } else {
+        v
+    }
+}
+
+/* Nearest neighbour blit with flips, rotation around the sprite center and scaling
+ * Walks every destination pixel in the rotated box and samples back into the image
+ * (x, y) is where the top left would be if it wasn't rotated
  */
-pub fn print_scr(pixels: &mut PixelsType, x: i32, y: i32, col: Colors, msg: String) {
-    for i in 0..msg.len() {
-        let c = msg.as_bytes().get(i).unwrap();
-        let mut idx: usize = (*c).into();
-        idx -= 32;
-        if idx >= BITMAP.len() {
-            idx = 0;
-        }
+pub fn draw_ex<T, P, R>(pixels: &mut T, x: i32, y: i32, img: &P, tf: &Transform)
+where
+    T: Target,
+    P: AsRef<[R]>,
+    R: AsRef<[Colors]>,
+{
+    let img = img.as_ref();
+    if img.is_empty() || img[0].as_ref().is_empty() {
+        return;
+    }
+    let w = img[0].as_ref().len() as f32;
+    let h = img.len() as f32;
 
-        for dx in 0..8 {
-            for (dy, row) in BITMAP[idx].iter().enumerate().take(8) {
-                if (row >> (7 - dx)) & 1 == 1 {
-                    set_pix(pixels, y + dy as i32, x + dx + i as i32 * 8, col);
-                }
+    //Negative scales are just flips
+    let flip_x = tf.flip_x ^ (tf.scale_x < 0.0);
+    let flip_y = tf.flip_y ^ (tf.scale_y < 0.0);
+    let (scale_x, scale_y) = (tf.scale_x.abs(), tf.scale_y.abs());
+    let (dw, dh) = (w * scale_x, h * scale_y);
+    if dw < 1.0 || dh < 1.0 {
+        return;
+    }
+
+    let (sin, cos) = tf.angle.to_radians().sin_cos();
+    let (sin, cos) = (snap(sin), snap(cos));
+    let cx = x as f32 + dw / 2.0;
+    let cy = y as f32 + dh / 2.0;
+    let ex = (dw * cos.abs() + dh * sin.abs()) / 2.0;
+    let ey = (dw * sin.abs() + dh * cos.abs()) / 2.0;
+
+    //Only the part of the box that's on the target, huge scales would take forever otherwise
+    let (bx, by, bw, bh) = pixels.bounds();
+    let (bx, by, bw, bh) = (bx as f32, by as f32, bw as f32, bh as f32);
+    let (x0, x1) = ((cx - ex).floor().max(bx), (cx + ex).ceil().min(bx + bw));
+    let (y0, y1) = ((cy - ey).floor().max(by), (cy + ey).ceil().min(by + bh));
+
+    for py in y0 as i32..y1 as i32 {
+        for px in x0 as i32..x1 as i32 {
+            let rx = px as f32 + 0.5 - cx;
+            let ry = py as f32 + 0.5 - cy;
+            let ux = rx * cos + ry * sin;
+            let uy = -rx * sin + ry * cos;
+
+            let src_x = (ux / scale_x + w / 2.0).floor();
+            let src_y = (uy / scale_y + h / 2.0).floor();
+            if src_x < 0.0 || src_y < 0.0 || src_x >= w || src_y >= h {
+                continue;
             }
+
+            let mut src_x = src_x as usize;
+            let mut src_y = src_y as usize;
+            if flip_x {
+                src_x = w as usize - 1 - src_x;
+            }
+            if flip_y {
+                src_y = h as usize - 1 - src_y;
+            }
+
+            blit_pix(pixels, py, px, img[src_y].as_ref()[src_x]);
         }
     }
 }
 
-pub fn print_scr_mini(pixels: &mut PixelsType, x: i32, y: i32, col: Colors, msg: String) {
-    for i in 0..msg.len() {
-        let c = msg.as_bytes().get(i).unwrap();
-        let mut idx: usize = (*c).into();
-        let orig_idx: usize = (*c).into();
-        idx -= 32;
-        idx /= 2;
-        idx *= 4;
+/* The sprite sheet as one big image, laid out like the sprite editor shows it
+ * (SPRITESHEET_COLS slots per row), anything past the end is blank
+ * Slots stay SPRITE_SIZE no matter the sprite so coordinates don't move around,
+ * smaller sprites sit in the top left and bigger ones get cut off
+ */
+pub fn atlas_pix(sheet: &[PixelsType], x: i32, y: i32) -> Colors {
+    if x < 0 || y < 0 {
+        return Colors::Blank;
+    }
+    let (x, y) = (x as usize, y as usize);
+    let cols = SPRITESHEET_COLS as usize;
+    if x >= cols * SPRITE_SIZE {
+        return Colors::Blank;
+    }
 
-        if idx >= BITMAP4X4.len() {
-            idx = 0;
+    let idx = (y / SPRITE_SIZE) * cols + x / SPRITE_SIZE;
+    match sheet.get(idx) {
+        Some(sprite) => sprite
+            .get(y % SPRITE_SIZE)
+            .and_then(|row| row.get(x % SPRITE_SIZE))
+            .copied()
+            .unwrap_or(Colors::Blank),
+        None => Colors::Blank,
+    }
+}
+
+//Copies (sx, sy, sw, sh) out of the atlas into (dx, dy, dw, dh), stretching nearest neighbour
+pub fn sspr<T: Target>(
+    pixels: &mut T,
+    sheet: &[PixelsType],
+    src: (i32, i32, i32, i32),
+    dst: (i32, i32, i32, i32),
+) {
+    let (sx, sy, sw, sh) = src;
+    let (dx, dy, dw, dh) = dst;
+    if sw <= 0 || sh <= 0 || dw <= 0 || dh <= 0 {
+        return;
+    }
+
+    //Only rows and columns that land on the target, i64 so big lua numbers can't overflow
+    let (bx, by, bw, bh) = pixels.bounds();
+    let (dx, dy, dw, dh) = (dx as i64, dy as i64, dw as i64, dh as i64);
+    let (j0, j1) = ((by as i64 - dy).max(0), (by as i64 + bh as i64 - dy).min(dh));
+    let (i0, i1) = ((bx as i64 - dx).max(0), (bx as i64 + bw as i64 - dx).min(dw));
+
+    for j in j0..j1 {
+        let src_y = sy as i64 + j * sh as i64 / dh;
+        for i in i0..i1 {
+            let src_x = sx as i64 + i * sw as i64 / dw;
+            let col = match (i32::try_from(src_x), i32::try_from(src_y)) {
+                (Ok(src_x), Ok(src_y)) => atlas_pix(sheet, src_x, src_y),
+                _ => Colors::Blank,
+            };
+            blit_pix(pixels, (dy + j) as i32, (dx + i) as i32, col);
         }
+    }
+}
+
+/* Draws cells (cx, cy, cw, ch) of one map layer with the top left cell at (sx, sy)
+ * Each cell shows the top left cell_size square of its sprite
+ * Cells outside the target never get looked at so drawing a whole big map stays cheap
+ */
+pub fn map<T: Target>(
+    pixels: &mut T,
+    sheet: &[PixelsType],
+    tilemap: &Tilemap,
+    layer: usize,
+    cells: (i32, i32, i32, i32),
+    pos: (i32, i32),
+) {
+    let (cx, cy, cw, ch) = cells;
+    let (sx, sy) = pos;
+    let size = tilemap.cell_size.clamp(1, i32::MAX as usize) as i64;
+    let (bx, by, bw, bh) = pixels.bounds();
+
+    //Only the cells that overlap the target, in i64 so huge lua numbers can't overflow
+    let cols = (bx as i64 - sx as i64).div_euclid(size).max(0)
+        ..(bx as i64 + bw as i64 - sx as i64 + size - 1).div_euclid(size).min(cw as i64);
+    let rows = (by as i64 - sy as i64).div_euclid(size).max(0)
+        ..(by as i64 + bh as i64 - sy as i64 + size - 1).div_euclid(size).min(ch as i64);
+
+    for j in rows {
+        let y = (sy as i64 + j * size) as i32;
+        let Ok(cell_y) = i32::try_from(cy as i64 + j) else {
+            continue;
+        };
+
+        for i in cols.clone() {
+            let x = (sx as i64 + i * size) as i32;
+            let Ok(cell_x) = i32::try_from(cx as i64 + i) else {
+                continue;
+            };
 
-        for dx in 0..4 {
-            for dy in 0..4 {
-                if ((BITMAP4X4[idx + dx] >> (3 - dy)) >> ((orig_idx & 1) * 4)) & 1 == 1 {
-                    set_pix(pixels, (y - dy) + 3, x + dx as i32 + i as i32 * 5, col);
+            let Some(sprite) = tilemap.get(layer, cell_x, cell_y).and_then(|idx| sheet.get(idx))
+            else {
+                continue;
+            };
+            for (dy, row) in sprite.iter().enumerate().take(size as usize) {
+                for (dx, col) in row.iter().enumerate().take(size as usize) {
+                    blit_pix(pixels, y + dy as i32, x + dx as i32, *col);
                 }
             }
         }
     }
 }
 
-pub fn print_scr_mid(pixels: &mut PixelsType, x: i32, y: i32, col: Colors, msg: String) {
-    for i in 0..msg.len() {
-        let c = msg.as_bytes().get(i).unwrap();
-        let mut idx: usize = (*c).into();
+//Thin wrappers so the editors don't need to care about fonts, see render::text
+pub fn print_scr<T: Target>(pixels: &mut T, x: i32, y: i32, col: Colors, msg: String) {
+    print_text(pixels, x, y, col, &msg, &TextStyle { font: Font::Big, ..TextStyle::default() });
+}
+
+pub fn print_scr_mini<T: Target>(pixels: &mut T, x: i32, y: i32, col: Colors, msg: String) {
+    print_text(pixels, x, y, col, &msg, &TextStyle { font: Font::Mini, ..TextStyle::default() });
+}
+
+pub fn print_scr_mid<T: Target>(pixels: &mut T, x: i32, y: i32, col: Colors, msg: String) {
+    print_text(pixels, x, y, col, &msg, &TextStyle { font: Font::Mid, ..TextStyle::default() });
+}
+
+pub fn rect_fill<T: Target>(pixels: &mut T, x: i32, y: i32, w: i32, h: i32, col: Colors) {
+    if w <= 0 {
+        return;
+    }
+    let (x, w) = (x as i64, w as i64);
+    for i in rows(pixels, y as i64, y as i64 + h as i64 - 1) {
+        hline(pixels, x, x + w - 1, i, col);
+    }
+}
+
+pub fn rect<T: Target>(pixels: &mut T, x: i32, y: i32, w: i32, h: i32, col: Colors) {
+    let (x, y, w, h) = (x as i64, y as i64, w as i64, h as i64);
+    if w > 0 {
+        span(pixels, x, x + w - 1, y, col, set_pix);
+        span(pixels, x, x + w - 1, y + h, col, set_pix);
+    }
+
+    for i in rows(pixels, y, y + h - 1) {
+        plot(pixels, x, i, col);
+        plot(pixels, x + w, i, col);
+    }
+
+    plot(pixels, x + w, y + h, col);
+}
+
+//Past this the midpoint loops take longer than a frame, so bigger shapes go row by row instead
+const MAX_MIDPOINT_RADIUS: i64 = 1 << 12;
+
+/* Clamps a row of pixels to the target before walking it, so shapes only cost what's visible
+ * Coords are i64 so lua numbers near the i32 limits can't overflow on the way here
+ */
+fn span<T: Target>(
+    pixels: &mut T,
+    x0: i64,
+    x1: i64,
+    y: i64,
+    col: Colors,
+    put: fn(&mut T, i32, i32, Colors),
+) {
+    let (bx, by, bw, bh) = pixels.bounds();
+    let (bx, by, bw, bh) = (bx as i64, by as i64, bw as i64, bh as i64);
+    if y < by || y >= by + bh {
+        return;
+    }
+    for x in x0.min(x1).max(bx)..=x0.max(x1).min(bx + bw - 1) {
+        put(pixels, y as i32, x as i32, col);
+    }
+}
+
+//Rows from y0 to y1 that are on the target, empty if none are
+fn rows<T: Target>(pixels: &T, y0: i64, y1: i64) -> std::ops::RangeInclusive<i64> {
+    let (_, by, _, bh) = pixels.bounds();
+    y0.max(by as i64)..=y1.min(by as i64 + bh as i64 - 1)
+}
+
+fn hline<T: Target>(pixels: &mut T, x0: i64, x1: i64, y: i64, col: Colors) {
+    span(pixels, x0, x1, y, col, fill_pix);
+}
+
+fn plot<T: Target>(pixels: &mut T, x: i64, y: i64, col: Colors) {
+    span(pixels, x, x, y, col, set_pix);
+}
+
+/* Half width of row dy for shapes too big for the midpoint loops, -1 if it's past the top
+ * Biggest x with x²ry² + dy²rx² <= rx²ry² + (rx²ry + ry²rx) / 2, the plain formula closest
+ * to what the midpoint versions draw, at this size the odd pixel of difference can't be seen
+ */
+fn big_ellipse_span(rx: i64, ry: i64, dy: i64) -> i64 {
+    let (rx, ry, dy) = (rx as i128, ry as i128, dy as i128);
+    let (a, b) = (rx * rx, ry * ry);
+    let lim = a * b + (a * ry + b * rx) / 2 - dy * dy * a;
+    if lim < 0 {
+        return -1;
+    }
+    if b == 0 {
+        return rx as i64;
+    }
+
+    let mut x = ((lim as f64) / (b as f64)).sqrt() as i128;
+    while x > 0 && x * x * b > lim {
+        x -= 1;
+    }
+    while (x + 1) * (x + 1) * b <= lim {
+        x += 1;
+    }
+    x.min(rx) as i64
+}
 
-        if idx >= BITMAP4X6.len() {
-            idx = 32;
+//Only the rows on the target get looked at, each one is worked out on its own
+fn big_ellipse_fill<T: Target>(pixels: &mut T, cx: i64, cy: i64, rx: i64, ry: i64, col: Colors) {
+    for y in rows(pixels, cy - ry, cy + ry) {
+        let w = big_ellipse_span(rx, ry, (y - cy).abs());
+        if w >= 0 {
+            hline(pixels, cx - w, cx + w, y, col);
         }
+    }
+}
 
-        for dx in 0..4 {
-            for (dy, row) in BITMAP4X6[idx].iter().enumerate().take(8) {
-                //println!("{} {}", idx, BI
-                if (row >> (3 - dx)) & 1 == 1 {
-                    set_pix(pixels, y + dy as i32, x + dx + i as i32 * 4, col);
-                }
-            }
+//Steep parts of the edge cover every x between this row's end and where the next row out ends
+fn big_ellipse_outline<T: Target>(pixels: &mut T, cx: i64, cy: i64, rx: i64, ry: i64, col: Colors) {
+    for y in rows(pixels, cy - ry, cy + ry) {
+        let dy = (y - cy).abs();
+        let w = big_ellipse_span(rx, ry, dy);
+        if w < 0 {
+            continue;
         }
+        let outer = if dy < ry { big_ellipse_span(rx, ry, dy + 1) } else { -1 };
+        let from = (outer + 1).min(w);
+        span(pixels, cx + from, cx + w, y, col, set_pix);
+        span(pixels, cx - w, cx - from, y, col, set_pix);
     }
 }
 
-pub fn rect_fill(pixels: &mut PixelsType, x: i32, y: i32, w: i32, h: i32, col: Colors) {
-    for j in x..x + w {
-        for i in y..y + h {
-            set_pix(pixels, i, j, col);
+//Midpoint circle, fills the span between each pair of mirrored points
+pub fn circle<T: Target>(pixels: &mut T, cx: i32, cy: i32, r: i32, col: Colors) {
+    if r < 0 {
+        return;
+    }
+    let (cx, cy, r) = (cx as i64, cy as i64, r as i64);
+    if r > MAX_MIDPOINT_RADIUS {
+        big_ellipse_fill(pixels, cx, cy, r, r, col);
+        return;
+    }
+
+    let mut x = r;
+    let mut y = 0;
+    let mut err = 1 - r;
+    while x >= y {
+        hline(pixels, cx - x, cx + x, cy + y, col);
+        hline(pixels, cx - x, cx + x, cy - y, col);
+        hline(pixels, cx - y, cx + y, cy + x, col);
+        hline(pixels, cx - y, cx + y, cy - x, col);
+
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+pub fn circle_outline<T: Target>(pixels: &mut T, cx: i32, cy: i32, r: i32, col: Colors) {
+    if r < 0 {
+        return;
+    }
+    let (cx, cy, r) = (cx as i64, cy as i64, r as i64);
+    if r > MAX_MIDPOINT_RADIUS {
+        big_ellipse_outline(pixels, cx, cy, r, r, col);
+        return;
+    }
+
+    let mut x = r;
+    let mut y = 0;
+    let mut err = 1 - r;
+    while x >= y {
+        for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+            plot(pixels, cx + dx, cy + dy, col);
+        }
+
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+/* Midpoint ellipse, walks the first quadrant in two regions
+ * (slope > -1 then slope < -1) and hands each point to plot to mirror
+ */
+fn ellipse_points<F>(rx: i32, ry: i32, mut plot: F)
+where
+    F: FnMut(i32, i32),
+{
+    if rx < 0 || ry < 0 {
+        return;
+    }
+    if ry == 0 {
+        for x in 0..=rx {
+            plot(x, 0);
+        }
+        return;
+    }
+
+    let rx2 = rx as i64 * rx as i64;
+    let ry2 = ry as i64 * ry as i64;
+    let mut x: i64 = 0;
+    let mut y: i64 = ry as i64;
+    let mut px: i64 = 0;
+    let mut py: i64 = 2 * rx2 * y;
+
+    let mut p = ry2 - rx2 * ry as i64 + rx2 / 4;
+    while px < py {
+        plot(x as i32, y as i32);
+        x += 1;
+        px += 2 * ry2;
+        if p < 0 {
+            p += ry2 + px;
+        } else {
+            y -= 1;
+            py -= 2 * rx2;
+            p += ry2 + px - py;
+        }
+    }
+
+    p = ry2 * (2 * x + 1) * (2 * x + 1) / 4 + rx2 * (y - 1) * (y - 1) - rx2 * ry2;
+    while y >= 0 {
+        plot(x as i32, y as i32);
+        y -= 1;
+        py -= 2 * rx2;
+        if p > 0 {
+            p += rx2 - py;
+        } else {
+            x += 1;
+            px += 2 * ry2;
+            p += rx2 - py + px;
+        }
+    }
+}
+
+pub fn ellipse<T: Target>(pixels: &mut T, cx: i32, cy: i32, rx: i32, ry: i32, col: Colors) {
+    let (cx, cy) = (cx as i64, cy as i64);
+    if rx as i64 > MAX_MIDPOINT_RADIUS || ry as i64 > MAX_MIDPOINT_RADIUS {
+        if rx >= 0 && ry >= 0 {
+            big_ellipse_outline(pixels, cx, cy, rx as i64, ry as i64, col);
+        }
+        return;
+    }
+
+    ellipse_points(rx, ry, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        plot(pixels, cx + x, cy + y, col);
+        plot(pixels, cx - x, cy + y, col);
+        plot(pixels, cx + x, cy - y, col);
+        plot(pixels, cx - x, cy - y, col);
+    });
+}
+
+pub fn ellipse_fill<T: Target>(pixels: &mut T, cx: i32, cy: i32, rx: i32, ry: i32, col: Colors) {
+    let (cx, cy) = (cx as i64, cy as i64);
+    if rx as i64 > MAX_MIDPOINT_RADIUS || ry as i64 > MAX_MIDPOINT_RADIUS {
+        if rx >= 0 && ry >= 0 {
+            big_ellipse_fill(pixels, cx, cy, rx as i64, ry as i64, col);
+        }
+        return;
+    }
+
+    ellipse_points(rx, ry, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        hline(pixels, cx - x, cx + x, cy + y, col);
+        hline(pixels, cx - x, cx + x, cy - y, col);
+    });
+}
+
+pub fn line<T: Target>(pixels: &mut T, x0: i32, y0: i32, x1: i32, y1: i32, col: Colors) {
+    let bounds = pixels.bounds();
+    line_points(bounds, x0, y0, x1, y1, |x, y| set_pix(pixels, y, x, col));
+}
+
+//Steps along the major axis that land in lo..=hi, a bit of slack on the minor one for the rounding
+fn steps_in(lo: i128, hi: i128, d: i128, major: i128) -> (i128, i128) {
+    if d == major {
+        (lo, hi)
+    } else if d == 0 {
+        if lo <= 0 && 0 <= hi {
+            (0, major)
+        } else {
+            (1, 0)
+        }
+    } else {
+        (((lo - 1) * major).div_euclid(d), ((hi + 1) * major).div_euclid(d) + 1)
+    }
+}
+
+/* Plain Bresenham, works in every octant
+ * Pixel k along the major axis is worked out directly so only the part inside bounds gets
+ * walked, a line way off the screen or billions of pixels long costs the same as a short one
+ */
+fn line_points<F>(bounds: (i32, i32, i32, i32), x0: i32, y0: i32, x1: i32, y1: i32, mut plot: F)
+where
+    F: FnMut(i32, i32),
+{
+    let (x0, y0, x1, y1) = (x0 as i128, y0 as i128, x1 as i128, y1 as i128);
+    let dx = (x1 - x0).abs();
+    let dy = (y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let major = dx.max(dy);
+
+    //Same rounding the error term does, halves go away from the start
+    let minor =
+        |d: i128, k: i128| if major == 0 { 0 } else { (2 * d * k + major).div_euclid(2 * major) };
+
+    let (bx, by, bw, bh) = bounds;
+    let (bx, by, bw, bh) = (bx as i128, by as i128, bw as i128, bh as i128);
+    let offsets = |start: i128, s: i128, b: i128, len: i128| {
+        if s > 0 {
+            (b - start, b + len - 1 - start)
+        } else {
+            (start - (b + len - 1), start - b)
         }
+    };
+    let (xlo, xhi) = offsets(x0, sx, bx, bw);
+    let (ylo, yhi) = offsets(y0, sy, by, bh);
+    let (xk0, xk1) = steps_in(xlo, xhi, dx, major);
+    let (yk0, yk1) = steps_in(ylo, yhi, dy, major);
+
+    for k in xk0.max(yk0).max(0)..=xk1.min(yk1).min(major) {
+        let x = x0 + sx * minor(dx, k);
+        let y = y0 + sy * minor(dy, k);
+        plot(x as i32, y as i32);
+    }
+}
+
+pub fn tri<T: Target>(pixels: &mut T, points: [(i32, i32); 3], col: Colors) {
+    poly_outline(pixels, &points, col);
+}
+
+pub fn tri_fill<T: Target>(pixels: &mut T, points: [(i32, i32); 3], col: Colors) {
+    poly(pixels, &points, col);
+}
+
+fn poly_outline<T: Target>(pixels: &mut T, points: &[(i32, i32)], col: Colors) {
+    for (i, (x0, y0)) in points.iter().enumerate() {
+        let (x1, y1) = points[(i + 1) % points.len()];
+        line(pixels, *x0, *y0, x1, y1, col);
     }
 }
 
-pub fn rect(pixels: &mut PixelsType, x: i32, y: i32, w: i32, h: i32, col: Colors) {
-    for i in x..x + w {
-        set_pix(pixels, y, i, col);
-        set_pix(pixels, y + h, i, col);
+/* Scanline fill with the even-odd rule so concave and self intersecting shapes work
+ * Rows are sampled at pixel centers which misses the bottom/right edges,
+ * so the outline gets stroked after to make sure the vertices are covered
+ */
+pub fn poly<T: Target>(pixels: &mut T, points: &[(i32, i32)], col: Colors) {
+    if points.len() < 3 {
+        poly_outline(pixels, points, col);
+        return;
     }
 
-    for i in y..y + h {
-        set_pix(pixels, i, x, col);
-        set_pix(pixels, i, x + w, col);
+    let bounds = pixels.bounds();
+    let (bx, by, bw, bh) = bounds;
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0).max(by);
+    let max_y = points.iter().map(|p| p.1).max().unwrap_or(0).min(by + bh - 1);
+
+    let mut crossings: Vec<i32> = Vec::new();
+    for y in min_y..=max_y {
+        let scan = y as f32 + 0.5;
+        crossings.clear();
+
+        for i in 0..points.len() {
+            let (x0, y0) = points[i];
+            let (x1, y1) = points[(i + 1) % points.len()];
+            let (fy0, fy1) = (y0 as f32, y1 as f32);
+            if (fy0 <= scan && fy1 > scan) || (fy1 <= scan && fy0 > scan) {
+                let t = (scan - fy0) / (fy1 - fy0);
+                crossings.push((x0 as f32 + t * (x1 as f32 - x0 as f32)).round() as i32);
+            }
+        }
+
+        crossings.sort_unstable();
+        for pair in crossings.chunks_exact(2) {
+            for x in pair[0].max(bx)..pair[1].min(bx.saturating_add(bw)) {
+                fill_pix(pixels, y, x, col);
+            }
+        }
     }
 
-    set_pix(pixels, y + h, x + w, col);
+    //Edges are part of the fill so they get the pattern too
+    for (i, (x0, y0)) in points.iter().enumerate() {
+        let (x1, y1) = points[(i + 1) % points.len()];
+        line_points(bounds, *x0, *y0, x1, y1, |x, y| fill_pix(pixels, y, x, col));
+    }
 }
 
-//This kinda gives weird results we might wanna switch out the algorithm at some point
-pub fn circle(pixels: &mut PixelsType, cx: i32, cy: i32, r: i32, col: Colors) {
-    let r2 = r * r;
-    for x in cx - r..=cx + r {
-        for y in cy - r..=cy + r {
-            let dx = x - cx;
-            let dy = y - cy;
-            if dx * dx + dy * dy <= r2 {
-                set_pix(pixels, y, x, col);
+/* Returns every cell connected to (y, x) with the same color
+ * Shared between the sprite editor fill tool and the lua screen fill
+ */
+pub fn flood_region<T: Target>(pixels: &T, y: i32, x: i32) -> Vec<(i32, i32)> {
+    let Some(col) = pixels.get_pix(y, x) else {
+        return Vec::new();
+    };
+
+    let (bx, by, bw, bh) = pixels.bounds();
+    let mut region = Vec::new();
+    let mut q: Vec<(i32, i32)> = vec![(y, x)];
+    let mut visited = vec![vec![false; bw as usize]; bh as usize];
+    while let Some(t) = q.pop() {
+        let (vy, vx) = ((t.0 - by) as usize, (t.1 - bx) as usize);
+        if visited[vy][vx] {
+            continue;
+        }
+        visited[vy][vx] = true;
+        region.push(t);
+
+        for dir in DIRS {
+            let ny = t.0 + dir.0;
+            let nx = t.1 + dir.1;
+            if pixels.get_pix(ny, nx) == Some(col) {
+                q.push((ny, nx));
             }
         }
     }
+
+    region
+}
+
+pub fn flood_fill<T: Target>(pixels: &mut T, x: i32, y: i32, col: Colors) {
+    for (ry, rx) in flood_region(pixels, y, x) {
+        fill_pix(pixels, ry, rx, col);
+    }
 }
 
-pub fn clear(pixels: &mut PixelsType, col: Colors) {
-    let height = pixels.len() as i32;
-    let width = pixels[0].len() as i32;
-    for y in 0..height {
-        for x in 0..width {
+pub fn clear<T: Target>(pixels: &mut T, col: Colors) {
+    let (bx, by, bw, bh) = pixels.bounds();
+    for y in by..by + bh {
+        for x in bx..bx + bw {
             set_pix(pixels, y, x, col);
         }
     }
@@ -209,6 +795,56 @@ pub fn image_from_tool(
             [bl, si, si, bl, si, si, bl, si, si, bl],
             [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
         ],
+        Tools::Pivot => [
+            [bl, bl, bl, bl, si, bl, bl, bl, bl, bl],
+            [bl, bl, bl, bl, si, bl, bl, bl, bl, bl],
+            [bl, bl, bl, bl, si, bl, bl, bl, bl, bl],
+            [bl, bl, bl, re, re, re, bl, bl, bl, bl],
+            [si, si, si, re, pi, re, si, si, si, bl],
+            [bl, bl, bl, re, re, re, bl, bl, bl, bl],
+            [bl, bl, bl, bl, si, bl, bl, bl, bl, bl],
+            [bl, bl, bl, bl, si, bl, bl, bl, bl, bl],
+            [bl, bl, bl, bl, si, bl, bl, bl, bl, bl],
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+        ],
+        Tools::Hitbox => [
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+            [bl, re, re, re, re, re, re, re, re, bl],
+            [bl, re, bl, bl, bl, bl, bl, bl, re, bl],
+            [bl, re, bl, si, si, si, si, bl, re, bl],
+            [bl, re, bl, si, gr, gr, si, bl, re, bl],
+            [bl, re, bl, si, gr, gr, si, bl, re, bl],
+            [bl, re, bl, si, si, si, si, bl, re, bl],
+            [bl, re, bl, bl, bl, bl, bl, bl, re, bl],
+            [bl, re, re, re, re, re, re, re, re, bl],
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+        ],
+    }
+}
+
+//Map editor shares the sprite editor's icons, plus the one tool only it has
+pub fn image_from_map_tool(
+    tool: MapTools,
+) -> [[Colors; BUTTON_WIDTH as usize - 2]; BUTTON_WIDTH as usize - 2] {
+    let bl = Colors::Blank;
+    let db = Colors::Blue;
+    let si = Colors::Silver;
+    match tool {
+        MapTools::Pencil => image_from_tool(Tools::Pencil),
+        MapTools::Fill => image_from_tool(Tools::Fill),
+        MapTools::Eraser => image_from_tool(Tools::Eraser),
+        MapTools::Rect => [
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+            [bl, db, db, db, db, db, db, db, db, bl],
+            [bl, db, si, si, si, si, si, si, db, bl],
+            [bl, db, si, si, si, si, si, si, db, bl],
+            [bl, db, si, si, si, si, si, si, db, bl],
+            [bl, db, si, si, si, si, si, si, db, bl],
+            [bl, db, si, si, si, si, si, si, db, bl],
+            [bl, db, si, si, si, si, si, si, db, bl],
+            [bl, db, db, db, db, db, db, db, db, bl],
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+        ],
     }
 }
 
@@ -219,6 +855,10 @@ pub fn image_from_util(
     let re = Colors::Red;
     let gr = Colors::Gray;
     let ge = Colors::Green;
+    let br = Colors::Brown;
+    let ye = Colors::Yellow;
+    let db = Colors::Blue;
+    let pi = Colors::Pink;
     match util {
         Utils::FlipHor => [
             [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
@@ -256,6 +896,30 @@ pub fn image_from_util(
             [bl, re, re, bl, bl, bl, bl, re, re, bl],
             [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
         ],
+        Utils::Palette => [
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+            [bl, bl, br, br, br, br, br, bl, bl, bl],
+            [bl, br, re, br, ye, br, br, br, bl, bl],
+            [br, br, br, br, br, br, db, br, br, bl],
+            [br, ge, br, br, bl, bl, br, br, br, bl],
+            [br, br, br, br, bl, bl, br, br, bl, bl],
+            [br, pi, br, br, br, br, br, bl, bl, bl],
+            [bl, br, br, br, br, br, br, br, bl, bl],
+            [bl, bl, br, br, br, br, br, br, br, bl],
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+        ],
+        Utils::Animation => [
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+            [gr, gr, gr, gr, gr, gr, gr, gr, gr, gr],
+            [gr, bl, gr, bl, gr, bl, gr, bl, gr, bl],
+            [gr, gr, gr, gr, gr, gr, gr, gr, gr, gr],
+            [gr, ye, ye, gr, db, db, gr, re, re, gr],
+            [gr, ye, ye, gr, db, db, gr, re, re, gr],
+            [gr, gr, gr, gr, gr, gr, gr, gr, gr, gr],
+            [gr, bl, gr, bl, gr, bl, gr, bl, gr, bl],
+            [gr, gr, gr, gr, gr, gr, gr, gr, gr, gr],
+            [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
+        ],
         Utils::Save => [
             [bl, bl, bl, bl, bl, bl, bl, bl, bl, bl],
             [bl, bl, bl, bl, bl, bl, bl, bl, ge, bl],
@@ -0,0 +1,105 @@
+use crate::render::{
+    colors::{ColorMap, Colors, ALL_COLORS},
+    pixels::Target,
+};
+
+//Everything lua can change about how draws land on the screen
+//Lives on the LuaAPI so it goes back to default whenever the game restarts
+#[derive(Debug, Clone, Copy)]
+pub struct DrawState {
+    pub camera: (i32, i32),
+    pub clip: Option<(i32, i32, i32, i32)>,
+    pub pal: ColorMap,
+    pub transparent: [bool; 17],
+    //4x4 pattern for filled shapes, bit 15 is the top left pixel, set bits use fill_alt
+    pub fill_pattern: u16,
+    pub fill_alt: Colors,
+}
+
+impl Default for DrawState {
+    fn default() -> Self {
+        let mut transparent = [false; 17];
+        transparent[Colors::Blank as usize] = true;
+        DrawState {
+            camera: (0, 0),
+            clip: None,
+            pal: ALL_COLORS,
+            transparent,
+            fill_pattern: 0,
+            fill_alt: Colors::Blank,
+        }
+    }
+}
+
+impl DrawState {
+    fn in_clip(&self, y: i32, x: i32) -> bool {
+        match self.clip {
+            Some((cx, cy, cw, ch)) => x >= cx && x < cx + cw && y >= cy && y < cy + ch,
+            None => true,
+        }
+    }
+
+    //Pattern is in screen space like the clip so it doesn't crawl when the camera moves
+    fn pattern_bit(&self, y: i32, x: i32) -> bool {
+        let bit = 15 - (y.rem_euclid(4) * 4 + x.rem_euclid(4));
+        (self.fill_pattern >> bit) & 1 == 1
+    }
+}
+
+/* Draw target for lua, shifts everything by the camera, remaps through the draw palette
+ * and drops pixels outside the clip
+ * Just borrows so we can build one per call without fighting the RefCell
+ */
+pub struct Screen<'a> {
+    pixels: &'a mut dyn Target,
+    state: &'a DrawState,
+}
+
+impl<'a> Screen<'a> {
+    pub fn new(pixels: &'a mut dyn Target, state: &'a DrawState) -> Self {
+        Screen { pixels, state }
+    }
+
+    //None when the camera pushes the point past what an i32 holds, it can't be on screen then
+    fn to_screen(&self, y: i32, x: i32) -> Option<(i32, i32)> {
+        Some((y.checked_sub(self.state.camera.1)?, x.checked_sub(self.state.camera.0)?))
+    }
+}
+
+impl Target for Screen<'_> {
+    fn get_pix(&self, y: i32, x: i32) -> Option<Colors> {
+        let (sy, sx) = self.to_screen(y, x)?;
+        self.pixels.get_pix(sy, sx)
+    }
+
+    fn put_pix(&mut self, y: i32, x: i32, col: Colors) {
+        let Some((sy, sx)) = self.to_screen(y, x) else {
+            return;
+        };
+        let col = self.state.pal[col as usize];
+        if col == Colors::Blank || !self.state.in_clip(sy, sx) {
+            return;
+        }
+
+        self.pixels.put_pix(sy, sx, col);
+    }
+
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        let (x, y, w, h) = self.pixels.bounds();
+        (x.saturating_add(self.state.camera.0), y.saturating_add(self.state.camera.1), w, h)
+    }
+
+    //Blank as the alt color leaves those pixels alone, put_pix already drops it
+    fn put_fill(&mut self, y: i32, x: i32, col: Colors) {
+        let Some((sy, sx)) = self.to_screen(y, x) else {
+            return;
+        };
+        let col = if self.state.pattern_bit(sy, sx) { self.state.fill_alt } else { col };
+        self.put_pix(y, x, col);
+    }
+
+    //Blank only draws once it's also remapped with pal, on its own it has no color
+    fn is_transparent(&self, col: Colors) -> bool {
+        self.state.transparent[col as usize]
+    }
+}
@@ -0,0 +1,290 @@
+use std::str::FromStr;
+
+use crate::{
+    render::{
+        bitmap::{BITMAP, BITMAP4X4, BITMAP4X6},
+        colors::Colors,
+        pixels::{set_pix, Target},
+    },
+    scripting::font::BitmapFont,
+};
+
+//Tab stops are every 4 characters from the start of the line
+const TAB_WIDTH: i32 = 4;
+//Drawn for anything the bitmaps don't have, non ascii and control bytes mostly
+const FALLBACK_GLYPH: char = '?';
+//Biggest scale lua can ask for, already fills the screen with a single glyph
+pub const MAX_TEXT_SCALE: i32 = 16;
+
+/* Big is the print_scr font, Mid and Mini go with print_scr_mid and print_scr_mini
+ * Custom borrows one of the cartridge fonts
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Font<'a> {
+    #[default]
+    Big,
+    Mid,
+    Mini,
+    Custom(&'a BitmapFont),
+}
+
+//Only the built in fonts, cartridge fonts get looked up by name
+impl FromStr for Font<'_> {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "BIG" => Ok(Font::Big),
+            "MID" => Ok(Font::Mid),
+            "MINI" => Ok(Font::Mini),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Font<'_> {
+    //How far the cursor moves after c, built in glyphs include their own spacing
+    pub fn advance(self, c: char) -> i32 {
+        match self {
+            Font::Big => 8,
+            Font::Mid => 4,
+            Font::Mini => 5,
+            Font::Custom(font) => match font.glyph(c) {
+                Some(glyph) => glyph.advance as i32,
+                None => font.width as i32 + 1,
+            },
+        }
+    }
+
+    pub fn line_height(self) -> i32 {
+        match self {
+            Font::Big => 8,
+            Font::Mid => 6,
+            Font::Mini => 5,
+            Font::Custom(font) => font.height as i32 + 1,
+        }
+    }
+
+    pub fn glyph_size(self) -> (i32, i32) {
+        match self {
+            Font::Big => (8, 8),
+            Font::Mid => (4, 6),
+            Font::Mini => (4, 4),
+            Font::Custom(font) => (font.width as i32, font.height as i32),
+        }
+    }
+
+    //Anything the font doesn't have gets the fallback instead of panicking
+    fn glyph(self, c: char) -> char {
+        let has_glyph = match self {
+            Font::Custom(font) => font.glyph(c).is_some(),
+            _ => (' '..='~').contains(&c),
+        };
+        if has_glyph {
+            c
+        } else {
+            FALLBACK_GLYPH
+        }
+    }
+
+    //c has to be printable ascii for the built in fonts, glyph() makes sure of that
+    pub fn glyph_pix(self, c: char, x: i32, y: i32) -> bool {
+        let code = c as usize;
+        match self {
+            Font::Big => (BITMAP[code - 32][y as usize] >> (7 - x)) & 1 == 1,
+            Font::Mid => (BITMAP4X6[code][y as usize] >> (3 - x)) & 1 == 1,
+            //Two characters share each group of 4 columns, odd ones live in the high nibble
+            Font::Mini => {
+                let col = BITMAP4X4[(code - 32) / 2 * 4 + x as usize];
+                (col >> (y as usize + (code & 1) * 4)) & 1 == 1
+            }
+            Font::Custom(font) => font.get(c, x, y),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl FromStr for Align {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "LEFT" => Ok(Align::Left),
+            "CENTER" => Ok(Align::Center),
+            "RIGHT" => Ok(Align::Right),
+            _ => Err(()),
+        }
+    }
+}
+
+//How print_text and print_box lay text out, like Transform for draw_ex
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle<'a> {
+    pub font: Font<'a>,
+    pub scale: i32,
+    pub align: Align,
+}
+
+impl Default for TextStyle<'_> {
+    fn default() -> Self {
+        TextStyle { font: Font::default(), scale: 1, align: Align::default() }
+    }
+}
+
+/* Walks a single line and hands back the unscaled x each glyph starts at
+ * Tabs jump to the next stop and carriage returns are dropped so \r\n text works
+ */
+fn layout<F>(line: &str, font: Font, mut place: F) -> i32
+where
+    F: FnMut(i32, char),
+{
+    let tab = (TAB_WIDTH * font.advance(' ')).max(1);
+    let mut x = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => x = (x / tab + 1) * tab,
+            '\r' => {}
+            _ => {
+                let c = font.glyph(c);
+                place(x, c);
+                x += font.advance(c);
+            }
+        }
+    }
+    x
+}
+
+fn line_width(line: &str, font: Font, scale: i32) -> i32 {
+    layout(line, font, |_, _| {}).saturating_mul(scale.max(1))
+}
+
+fn print_line<T: Target>(
+    pixels: &mut T,
+    x: i32,
+    y: i32,
+    col: Colors,
+    line: &str,
+    font: Font,
+    scale: i32,
+) {
+    let scale = scale.max(1) as i64;
+    let (w, h) = font.glyph_size();
+    //Only the part of each scaled up pixel that's on the target gets looped over
+    let (bx, by, bw, bh) = pixels.bounds();
+    let (bx, by, bw, bh) = (bx as i64, by as i64, bw as i64, bh as i64);
+    layout(line, font, |lx, c| {
+        let gx = x as i64 + lx as i64 * scale;
+        for dy in 0..h {
+            for dx in 0..w {
+                if !font.glyph_pix(c, dx, dy) {
+                    continue;
+                }
+
+                let (px, py) = (gx + dx as i64 * scale, y as i64 + dy as i64 * scale);
+                for sy in py.max(by)..(py + scale).min(by + bh) {
+                    for sx in px.max(bx)..(px + scale).min(bx + bw) {
+                        set_pix(pixels, sy as i32, sx as i32, col);
+                    }
+                }
+            }
+        }
+    });
+}
+
+//Width of the widest line
+pub fn text_width(msg: &str, font: Font, scale: i32) -> i32 {
+    msg.split('\n').map(|line| line_width(line, font, scale)).max().unwrap_or(0)
+}
+
+pub fn text_height(msg: &str, font: Font, scale: i32) -> i32 {
+    msg.split('\n').count() as i32 * font.line_height() * scale.max(1)
+}
+
+/* Every line of msg gets aligned on its own around x
+ * Left starts at x, Center is centered on x, Right ends at x
+ */
+pub fn print_text<T: Target>(
+    pixels: &mut T,
+    x: i32,
+    y: i32,
+    col: Colors,
+    msg: &str,
+    style: &TextStyle,
+) {
+    let TextStyle { font, align, .. } = *style;
+    let scale = style.scale.max(1);
+    for (i, line) in msg.split('\n').enumerate() {
+        let w = line_width(line, font, scale);
+        let lx = match align {
+            Align::Left => x,
+            Align::Center => x - w / 2,
+            Align::Right => x - w,
+        };
+        print_line(pixels, lx, y + i as i32 * font.line_height() * scale, col, line, font, scale);
+    }
+}
+
+/* Greedy word wrap, words that don't fit on a line by themselves get split
+ * wherever they run out of room so nothing ever sticks out of the box
+ */
+pub fn wrap_text(msg: &str, font: Font, scale: i32, width: i32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for para in msg.split('\n') {
+        let mut line = String::new();
+        for word in para.split(' ') {
+            let joined = if line.is_empty() { word.to_string() } else { format!("{line} {word}") };
+            if line_width(&joined, font, scale) <= width {
+                line = joined;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if line.chars().count() > 1 && line_width(&line, font, scale) > width {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+//Wraps msg inside the box and aligns each line in it, lines that don't fit in h get dropped
+pub fn print_box<T: Target>(
+    pixels: &mut T,
+    bounds: (i32, i32, i32, i32),
+    col: Colors,
+    msg: &str,
+    style: &TextStyle,
+) {
+    let TextStyle { font, align, .. } = *style;
+    let (x, y, w, h) = bounds;
+    let scale = style.scale.max(1);
+    let line_height = font.line_height() * scale;
+    for (i, line) in wrap_text(msg, font, scale, w).iter().enumerate() {
+        let ly = y + i as i32 * line_height;
+        if ly + line_height > y + h {
+            break;
+        }
+
+        let lw = line_width(line, font, scale);
+        let lx = match align {
+            Align::Left => x,
+            Align::Center => x + (w - lw) / 2,
+            Align::Right => x + w - lw,
+        };
+        print_line(pixels, lx, ly, col, line, font, scale);
+    }
+}
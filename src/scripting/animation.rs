@@ -0,0 +1,157 @@
+use bincode::{Decode, Encode};
+use mlua::UserData;
+
+//What new frames start at, 10 fps
+pub const DEFAULT_FRAME_MS: u32 = 100;
+pub const MAX_FRAME_MS: u32 = 10_000;
+
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimMode {
+    #[default]
+    Loop,
+    //Forward then back without showing the end frames twice
+    PingPong,
+    //Stops on the last frame
+    Once,
+}
+
+impl AnimMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimMode::Loop => "Loop",
+            AnimMode::PingPong => "Ping-pong",
+            AnimMode::Once => "Once",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            AnimMode::Loop => AnimMode::PingPong,
+            AnimMode::PingPong => AnimMode::Once,
+            AnimMode::Once => AnimMode::Loop,
+        }
+    }
+}
+
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimFrame {
+    pub sprite: usize,
+    //How long it stays up in milliseconds, same unit as update's dt
+    pub ms: u32,
+}
+
+/* Named run of sprites from the sheet, lives in the cart
+ * Nothing here keeps time, callers just ask which frame is up t ms in
+ */
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Animation {
+    pub name: String,
+    pub mode: AnimMode,
+    pub frames: Vec<AnimFrame>,
+}
+
+impl Animation {
+    pub fn new(name: String) -> Self {
+        Animation { name, ..Default::default() }
+    }
+
+    //Order frames get shown in for one pass, ping-pong comes back down without the ends
+    fn sequence(&self) -> impl Iterator<Item = usize> {
+        let n = self.frames.len();
+        let back = if self.mode == AnimMode::PingPong && n > 2 { 1..n - 1 } else { 0..0 };
+        (0..n).chain(back.rev())
+    }
+
+    fn frame_ms(&self, frame: usize) -> u64 {
+        self.frames[frame].ms.max(1) as u64
+    }
+
+    //Length of one pass in ms
+    pub fn length(&self) -> u64 {
+        self.sequence().map(|i| self.frame_ms(i)).sum()
+    }
+
+    pub fn finished(&self, t: u64) -> bool {
+        self.mode == AnimMode::Once && !self.frames.is_empty() && t >= self.length()
+    }
+
+    //Which of frames is up t ms in, None if there aren't any
+    pub fn frame_at(&self, t: u64) -> Option<usize> {
+        let length = self.length();
+        if length == 0 {
+            return None;
+        }
+
+        let mut t = match self.mode {
+            AnimMode::Once => t.min(length - 1),
+            _ => t % length,
+        };
+        for i in self.sequence() {
+            let ms = self.frame_ms(i);
+            if t < ms {
+                return Some(i);
+            }
+            t -= ms;
+        }
+        None
+    }
+
+    pub fn sprite_at(&self, t: u64) -> Option<usize> {
+        self.frame_at(t).map(|i| self.frames[i].sprite)
+    }
+}
+
+/* What rico:anim gives back, a copy of the clip with its own clock
+ * Lets every enemy run the same clip out of step without the game tracking time
+ */
+#[derive(Debug, Clone)]
+pub struct AnimPlayer {
+    pub anim: Animation,
+    pub t: f64,
+    pub speed: f64,
+}
+
+impl AnimPlayer {
+    pub fn new(anim: Animation) -> Self {
+        AnimPlayer { anim, t: 0.0, speed: 1.0 }
+    }
+
+    fn time(&self) -> u64 {
+        self.t.max(0.0) as u64
+    }
+
+    pub fn sprite(&self) -> Option<usize> {
+        self.anim.sprite_at(self.time())
+    }
+}
+
+impl UserData for AnimPlayer {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.anim.name.clone()));
+        fields.add_field_method_get("t", |_, this| Ok(this.t));
+        fields.add_field_method_set("t", |_, this, t: f64| {
+            this.t = t;
+            Ok(())
+        });
+        fields.add_field_method_get("speed", |_, this| Ok(this.speed));
+        fields.add_field_method_set("speed", |_, this, speed: f64| {
+            this.speed = speed;
+            Ok(())
+        });
+    }
+
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        //Straight from update(dt), speed scales it
+        methods.add_method_mut("update", |_, this, dt: f64| {
+            this.t += dt * this.speed;
+            Ok(())
+        });
+        methods.add_method("sprite", |_, this, ()| Ok(this.sprite()));
+        methods.add_method("frame", |_, this, ()| Ok(this.anim.frame_at(this.time())));
+        methods.add_method("finished", |_, this, ()| Ok(this.anim.finished(this.time())));
+        methods.add_method_mut("reset", |_, this, ()| {
+            this.t = 0.0;
+            Ok(())
+        });
+    }
+}
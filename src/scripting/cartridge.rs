@@ -3,26 +3,73 @@ use std::{
     error::Error,
     fs,
     io::{Read, Write},
-    path::Path,
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 
 use crate::{
-    engine::{rico::PixelsType, sprite::SPRITE_SIZE},
-    render::colors::Colors,
+    engine::{
+        rico::PixelsType,
+        sprite::{SPRITE_SIZE, SPRITE_SIZES},
+    },
+    render::colors::{Colors, Palette, DEFAULT_PALETTE},
+    scripting::{
+        animation::Animation,
+        font::{BitmapFont, ASCII_GLYPHS, EXTENDED_GLYPHS, MAX_GLYPH_SIZE},
+        sprite_info::SpriteInfo,
+        tilemap::{Tilemap, MAX_MAP_CELLS},
+    },
 };
-use bincode::{config::standard, Decode, Encode};
+use bincode::{config::standard, de::Decoder, error::DecodeError, Decode, Encode};
 use walkdir::WalkDir;
 
-#[derive(Encode, Decode, Debug, Clone)]
+/* New fields always go at the end, and need a default
+ * Decode is written by hand so carts from before a field existed still load
+ */
+#[derive(Encode, Debug, Clone)]
 pub struct Cartridge {
     pub sprite_sheet: Vec<PixelsType>,
     pub scripts: HashMap<String, String>,
+    pub palette: Option<Palette>,
+    pub map: Tilemap,
+    pub fonts: Vec<BitmapFont>,
+    //Same order as sprite_sheet, can be shorter for older carts
+    pub sprite_info: Vec<SpriteInfo>,
+    pub animations: Vec<Animation>,
 }
 
+//Older carts just end early, so running out of bytes means the field wasn't there yet
+fn decode_or_default<T, C, D>(decoder: &mut D) -> Result<T, DecodeError>
+where
+    T: Decode<C> + Default,
+    D: Decoder<Context = C>,
+{
+    match T::decode(decoder) {
+        Err(DecodeError::UnexpectedEnd { .. }) => Ok(T::default()),
+        res => res,
+    }
+}
+
+impl<C> Decode<C> for Cartridge {
+    fn decode<D: Decoder<Context = C>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Cartridge {
+            sprite_sheet: Decode::decode(decoder)?,
+            scripts: Decode::decode(decoder)?,
+            palette: decode_or_default(decoder)?,
+            map: decode_or_default(decoder)?,
+            fonts: decode_or_default(decoder)?,
+            sprite_info: decode_or_default(decoder)?,
+            animations: decode_or_default(decoder)?,
+        })
+    }
+}
+bincode::impl_borrow_decode!(Cartridge);
+
 pub const PATH: &str = "r32/";
 const BIN_PATH: &str = "main.r32";
+const DEFAULT_SPRITES: usize = 60;
 
 const HELLO_WORLD: &str = "
 function start()
@@ -40,13 +87,78 @@ function update(dt)
     end
 end";
 
+impl Cartridge {
+    //For carts that come from somewhere else, the editors count on all of this holding
+    pub fn validated(self) -> Result<Self, Box<dyn Error>> {
+        if !self.scripts.contains_key("main.lua") {
+            return Err("Cartridge has no main.lua".into());
+        }
+        if self.sprite_sheet.is_empty() {
+            return Err("Cartridge has no sprites".into());
+        }
+        let bad = self.sprite_sheet.iter().position(|sprite| {
+            !SPRITE_SIZES.contains(&sprite.len())
+                || sprite.iter().any(|row| row.len() != sprite.len())
+        });
+        if let Some(idx) = bad {
+            return Err(format!("Sprite {} isn't a size RICO-32 supports", idx).into());
+        }
+        if let Some(name) = self.scripts.keys().find(|name| !safe_script_path(name)) {
+            return Err(format!("Script {} would be written outside of {}", name, PATH).into());
+        }
+
+        let map = &self.map;
+        let cells = map.width.checked_mul(map.height).filter(|cells| *cells <= MAX_MAP_CELLS);
+        let Some(cells) = cells.filter(|cells| *cells > 0) else {
+            return Err(format!("Map size {}x{} isn't supported", map.width, map.height).into());
+        };
+        //Cells show part of one sprite, so bigger than the biggest sprite makes no sense
+        if map.cell_size == 0 || map.cell_size > SPRITE_SIZES[SPRITE_SIZES.len() - 1] {
+            return Err(format!("Map cell size {} isn't supported", map.cell_size).into());
+        }
+        if map.layers.is_empty() || map.layers.iter().any(|layer| layer.len() != cells) {
+            return Err("Map layers don't match the map size".into());
+        }
+
+        for font in &self.fonts {
+            let size_ok = |size: usize| (1..=MAX_GLYPH_SIZE).contains(&size);
+            if !size_ok(font.width) || !size_ok(font.height) {
+                return Err(format!("Font {} has an unsupported glyph size", font.name).into());
+            }
+            let glyphs_ok = [ASCII_GLYPHS, EXTENDED_GLYPHS].contains(&font.glyphs.len());
+            let pixels = font.width * font.height;
+            if !glyphs_ok || font.glyphs.iter().any(|glyph| glyph.pixels.len() != pixels) {
+                return Err(format!("Font {} has broken glyphs", font.name).into());
+            }
+        }
+        Ok(self)
+    }
+}
+
+/* Script names become paths under PATH when the cart gets extracted
+ * Only plain names and folders, anything like ../ or / could land anywhere on disk
+ */
+fn safe_script_path(name: &str) -> bool {
+    let path = Path::new(name);
+    path.components().next().is_some()
+        && path.components().all(|part| matches!(part, Component::Normal(_)))
+}
+
 impl Default for Cartridge {
     fn default() -> Self {
         let mut scripts = HashMap::new();
         scripts.insert("main.lua".to_string(), HELLO_WORLD.to_string());
         Cartridge {
-            sprite_sheet: vec![vec![vec![Colors::Blank; SPRITE_SIZE]; SPRITE_SIZE]; 60],
+            sprite_sheet: vec![
+                vec![vec![Colors::Blank; SPRITE_SIZE]; SPRITE_SIZE];
+                DEFAULT_SPRITES
+            ],
             scripts,
+            palette: None,
+            map: Tilemap::default(),
+            fonts: Vec::new(),
+            sprite_info: vec![SpriteInfo::default(); DEFAULT_SPRITES],
+            animations: Vec::new(),
         }
     }
 }
@@ -61,7 +173,12 @@ fn write_cart(cart: &Cartridge) -> Result<(), Box<dyn Error>> {
 }
 
 fn load_file() -> Result<Cartridge, Box<dyn Error>> {
-    let compressed_bytes = fs::read(BIN_PATH)?;
+    read_cart(Path::new(BIN_PATH))
+}
+
+//Any cart file, not just main.r32, nothing is checked past it decoding
+pub fn read_cart(path: &Path) -> Result<Cartridge, Box<dyn Error>> {
+    let compressed_bytes = fs::read(path)?;
     let mut decoder = GzDecoder::new(&compressed_bytes[..]);
     let mut decompressed = Vec::new();
     decoder.read_to_end(&mut decompressed)?;
@@ -81,6 +198,26 @@ pub fn get_cart() -> Result<Cartridge, Box<dyn Error>> {
     }
 }
 
+//Swaps main.r32 for another cart, the old one gets kept next to it in case it was still needed
+pub fn replace_cart(cart: &Cartridge) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let backup = if Path::new(BIN_PATH).exists() {
+        let backup = capture_path("r32");
+        fs::copy(BIN_PATH, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+    write_cart(cart)?;
+    Ok(backup)
+}
+
+//Screenshots and gifs go next to the cart, named after it and when they were taken
+pub fn capture_path(ext: &str) -> PathBuf {
+    let stem = Path::new(BIN_PATH).file_stem().and_then(|s| s.to_str()).unwrap_or("rico");
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    Path::new(BIN_PATH).with_file_name(format!("{stem}-{millis}.{ext}"))
+}
+
 pub fn load_cartridge() -> Result<Cartridge, Box<dyn Error>> {
     let cart = get_cart()?;
 
@@ -88,8 +225,12 @@ pub fn load_cartridge() -> Result<Cartridge, Box<dyn Error>> {
         fs::remove_dir_all(PATH)?;
     }
     for (file, content) in &cart.scripts {
-        let f_path = PATH.to_owned() + file;
-        if let Some(parent) = Path::new(&f_path).parent() {
+        //validated already turns these away, this is for carts that never went through it
+        if !safe_script_path(file) {
+            return Err(format!("Script {} would be written outside of {}", file, PATH).into());
+        }
+        let f_path = Path::new(PATH).join(file);
+        if let Some(parent) = f_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
@@ -106,6 +247,42 @@ pub fn update_sprites(sprite_sheet: &[PixelsType]) -> Result<(), Box<dyn Error>>
     Ok(())
 }
 
+pub fn update_sprite_info(sprite_info: &[SpriteInfo]) -> Result<(), Box<dyn Error>> {
+    let mut cart = get_cart()?;
+    cart.sprite_info = sprite_info.to_vec();
+    write_cart(&cart)?;
+    Ok(())
+}
+
+pub fn update_animations(animations: &[Animation]) -> Result<(), Box<dyn Error>> {
+    let mut cart = get_cart()?;
+    cart.animations = animations.to_vec();
+    write_cart(&cart)?;
+    Ok(())
+}
+
+pub fn update_palette(palette: &Palette) -> Result<(), Box<dyn Error>> {
+    let mut cart = get_cart()?;
+    //Default palette is stored as None so the cart stays the same as before palettes existed
+    cart.palette = (*palette != DEFAULT_PALETTE).then_some(*palette);
+    write_cart(&cart)?;
+    Ok(())
+}
+
+pub fn update_map(map: &Tilemap) -> Result<(), Box<dyn Error>> {
+    let mut cart = get_cart()?;
+    cart.map = map.clone();
+    write_cart(&cart)?;
+    Ok(())
+}
+
+pub fn update_fonts(fonts: &[BitmapFont]) -> Result<(), Box<dyn Error>> {
+    let mut cart = get_cart()?;
+    cart.fonts = fonts.to_vec();
+    write_cart(&cart)?;
+    Ok(())
+}
+
 pub fn update_scripts() -> Result<(), Box<dyn Error>> {
     let mut cart = get_cart()?;
     cart.scripts.clear();
@@ -0,0 +1,101 @@
+use crate::{engine::rico::PixelsType, render::colors::Colors};
+
+//x, y, w, h
+pub type Rect = (i32, i32, i32, i32);
+
+//Columns past this just don't collide, the editor tops out at 64 anyway
+const MASK_WIDTH: usize = u64::BITS as usize;
+
+pub fn intersect(a: Rect, b: Rect) -> Option<Rect> {
+    let x = a.0.max(b.0);
+    let y = a.1.max(b.1);
+    let w = (a.0 + a.2).min(b.0 + b.2) - x;
+    let h = (a.1 + a.3).min(b.1 + b.3) - y;
+    (w > 0 && h > 0).then_some((x, y, w, h))
+}
+
+fn width_bits(w: i32) -> u64 {
+    if w as usize >= MASK_WIDTH {
+        u64::MAX
+    } else {
+        (1 << w) - 1
+    }
+}
+
+/* Which pixels of a sprite aren't Blank, bit x of a row is column x
+ * Built once when the game starts since sprites can't change while it runs
+ * so a collision check is a handful of ands instead of a loop over every pixel
+ */
+#[derive(Debug, Clone)]
+pub struct SpriteMask {
+    rows: Vec<u64>,
+    //Smallest box around the opaque pixels, None for an empty sprite
+    bounds: Option<Rect>,
+}
+
+impl SpriteMask {
+    pub fn new(sprite: &PixelsType) -> Self {
+        let rows: Vec<u64> = sprite
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .take(MASK_WIDTH)
+                    .enumerate()
+                    .filter(|(_, col)| **col != Colors::Blank)
+                    .fold(0, |bits, (x, _)| bits | 1 << x)
+            })
+            .collect();
+
+        let all = rows.iter().fold(0, |all, row| all | row);
+        let top = rows.iter().position(|row| *row != 0);
+        let bottom = rows.iter().rposition(|row| *row != 0);
+        let bounds = top.zip(bottom).map(|(top, bottom)| {
+            let left = all.trailing_zeros() as i32;
+            let right = (MASK_WIDTH - all.leading_zeros() as usize) as i32;
+            (left, top as i32, right - left, (bottom - top + 1) as i32)
+        });
+
+        SpriteMask { rows, bounds }
+    }
+
+    pub fn bounds(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    //Opaque bounds moved to where the sprite is drawn
+    fn placed(&self, pos: (i32, i32)) -> Option<Rect> {
+        self.bounds.map(|(x, y, w, h)| (pos.0 + x, pos.1 + y, w, h))
+    }
+
+    //Bits of row y starting at column x, so bit 0 lines up with x
+    fn row_from(&self, y: i32, x: i32) -> u64 {
+        self.rows[y as usize].checked_shr(x as u32).unwrap_or(0)
+    }
+
+    //Only ever looks at the rows and columns where the two overlap
+    pub fn overlaps(&self, pos: (i32, i32), other: &SpriteMask, other_pos: (i32, i32)) -> bool {
+        let (Some(a), Some(b)) = (self.placed(pos), other.placed(other_pos)) else {
+            return false;
+        };
+        let Some((x, y, w, h)) = intersect(a, b) else {
+            return false;
+        };
+
+        let bits = width_bits(w);
+        (y..y + h).any(|row| {
+            self.row_from(row - pos.1, x - pos.0)
+                & other.row_from(row - other_pos.1, x - other_pos.0)
+                & bits
+                != 0
+        })
+    }
+
+    pub fn overlaps_rect(&self, pos: (i32, i32), rect: Rect) -> bool {
+        let Some((x, y, w, h)) = self.placed(pos).and_then(|a| intersect(a, rect)) else {
+            return false;
+        };
+
+        let bits = width_bits(w);
+        (y..y + h).any(|row| self.row_from(row - pos.1, x - pos.0) & bits != 0)
+    }
+}
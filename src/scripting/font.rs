@@ -0,0 +1,92 @@
+use bincode::{Decode, Encode};
+
+//Glyphs start at space, the control characters before it never get drawn
+pub const FIRST_GLYPH: u32 = 32;
+//Space to ~
+pub const ASCII_GLYPHS: usize = 95;
+//Everything up to the end of latin-1 so accented letters work
+pub const EXTENDED_GLYPHS: usize = 224;
+pub const MAX_GLYPH_SIZE: usize = 16;
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct Glyph {
+    //How far the cursor moves after this glyph, so thin letters can be thin
+    pub advance: usize,
+    //width * height, row by row
+    pub pixels: Vec<bool>,
+}
+
+/* A font drawn in the font editor, every glyph shares the same box
+ * glyphs[i] is the character FIRST_GLYPH + i
+ */
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct BitmapFont {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub glyphs: Vec<Glyph>,
+}
+
+impl BitmapFont {
+    pub fn new(name: String, width: usize, height: usize) -> Self {
+        let width = width.clamp(1, MAX_GLYPH_SIZE);
+        let height = height.clamp(1, MAX_GLYPH_SIZE);
+        let glyph = Glyph { advance: width + 1, pixels: vec![false; width * height] };
+        BitmapFont { name, width, height, glyphs: vec![glyph; ASCII_GLYPHS] }
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        let idx = (c as u32).checked_sub(FIRST_GLYPH)?;
+        self.glyphs.get(idx as usize)
+    }
+
+    pub fn get(&self, c: char, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return false;
+        }
+        let i = y as usize * self.width + x as usize;
+        self.glyph(c).is_some_and(|g| g.pixels.get(i).copied().unwrap_or(false))
+    }
+
+    pub fn set(&mut self, idx: usize, x: i32, y: i32, on: bool) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let i = y as usize * self.width + x as usize;
+        if let Some(pix) = self.glyphs.get_mut(idx).and_then(|g| g.pixels.get_mut(i)) {
+            *pix = on;
+        }
+    }
+
+    //Keeps whatever still fits in the top left, advances move by as much as the width did
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let width = width.clamp(1, MAX_GLYPH_SIZE);
+        let height = height.clamp(1, MAX_GLYPH_SIZE);
+        for glyph in &mut self.glyphs {
+            let mut pixels = vec![false; width * height];
+            for y in 0..height.min(self.height) {
+                for x in 0..width.min(self.width) {
+                    pixels[y * width + x] =
+                        glyph.pixels.get(y * self.width + x).copied().unwrap_or(false);
+                }
+            }
+            glyph.pixels = pixels;
+            glyph.advance =
+                (glyph.advance as i32 + width as i32 - self.width as i32).max(1) as usize;
+        }
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn extended(&self) -> bool {
+        self.glyphs.len() > ASCII_GLYPHS
+    }
+
+    //Dropping back to ascii throws away anything drawn in the extended range
+    pub fn set_extended(&mut self, extended: bool) {
+        let len = if extended { EXTENDED_GLYPHS } else { ASCII_GLYPHS };
+        let blank =
+            Glyph { advance: self.width + 1, pixels: vec![false; self.width * self.height] };
+        self.glyphs.resize(len, blank);
+    }
+}
@@ -2,24 +2,44 @@ use crate::engine::rico::ScreenEngine;
 use macro_procs::ScreenEngine;
 use mlua::prelude::LuaResult;
 use std::rc::Rc;
-use std::{cell::RefCell, fmt};
+use std::{cell::RefCell, error::Error, fmt, path::PathBuf};
+use winit::event::{ElementState, VirtualKeyCode};
 
-use mlua::UserData;
+use mlua::{Either, FromLua, IntoLuaMulti, Lua, UserData, UserDataFields, Value};
 
 use crate::{
     engine::{
         game::BASE_FPS,
-        rico::{PixelsType, SCREEN_SIZE},
+        rico::{PixelsType, SCALE, SCREEN_SIZE},
     },
     input::{
-        keyboard::{key_from_str, Keyboard},
+        controller::{Button, Controls, PLAYERS},
+        keyboard::{key_from_str, Keyboard, REPEAT_DELAY, REPEAT_INTERVAL},
         mouse::MousePress,
     },
     render::{
-        colors::Colors,
+        capture::{save_png, GifRecorder},
+        colors::{ColorMap, Colors, Palette, ALL_COLORS, DEFAULT_PALETTE},
+        cursor::{Cursor, CursorLayer},
+        framebuffer::{palette_lut, Framebuffer, PaletteLut},
         pixels::{
-            circle, clear, draw, print_scr, print_scr_mid, print_scr_mini, rect, rect_fill, set_pix,
+            circle, circle_outline, clear, draw, draw_ex, ellipse, ellipse_fill, flood_fill, line,
+            map, poly, print_scr_mid, print_scr_mini, rect, rect_fill, set_pix, sspr, tri,
+            tri_fill, Target, Transform,
         },
+        screen::{DrawState, Screen},
+        text::{
+            print_box, print_text, text_height, text_width, wrap_text, Align, Font, TextStyle,
+            MAX_TEXT_SCALE,
+        },
+    },
+    scripting::{
+        animation::{AnimPlayer, Animation},
+        cartridge::Cartridge,
+        collision::{intersect, SpriteMask},
+        font::BitmapFont,
+        sprite_info::{fit_sprite_info, SpriteInfo, SPRITE_FLAGS},
+        tilemap::Tilemap,
     },
 };
 
@@ -45,21 +65,131 @@ impl fmt::Display for LogTypes {
 pub struct LuaAPI {
     pub mouse: MousePress,
     pub keyboard: Keyboard,
+    pub controls: Controls,
     pub frame_rate: i32,
-    pub pixels: PixelsType,
+    pub pixels: Framebuffer,
+    pub draw_state: DrawState,
+    pub display_pal: ColorMap,
+    pub palette: Palette,
     pub sprites: Vec<PixelsType>,
+    pub sprite_info: Vec<SpriteInfo>,
+    //One per sprite, for collide and friends
+    pub masks: Vec<SpriteMask>,
+    pub animations: Vec<Animation>,
+    pub map: Tilemap,
+    pub fonts: Vec<BitmapFont>,
+    //Freed canvases leave a None so handles never move, canvas_new fills the gaps first
+    pub canvases: Vec<Option<PixelsType>>,
+    //Canvas every draw goes into, None is the screen
+    pub target: Option<usize>,
     pub logs: Vec<LogTypes>,
+    pub recorder: Option<GifRecorder>,
+    pub cursor: Cursor,
+    cursor_layer: CursorLayer,
 }
 
 impl LuaAPI {
-    pub fn new(sprite_sheet: Vec<PixelsType>) -> Self {
+    //Takes whatever the lua side needs out of the cart, scripts go to the ScriptEngine instead
+    pub fn new(cart: Cartridge) -> Self {
+        let mut sprite_info = cart.sprite_info;
+        fit_sprite_info(&mut sprite_info, cart.sprite_sheet.len());
+        let masks = cart.sprite_sheet.iter().map(SpriteMask::new).collect();
         LuaAPI {
             frame_rate: BASE_FPS,
-            pixels: Colors::pixels(SCREEN_SIZE, SCREEN_SIZE),
+            pixels: Framebuffer::new(SCREEN_SIZE, SCREEN_SIZE),
+            draw_state: DrawState::default(),
+            display_pal: ALL_COLORS,
+            palette: cart.palette.unwrap_or(DEFAULT_PALETTE),
             logs: Vec::new(),
-            sprites: sprite_sheet,
+            sprites: cart.sprite_sheet,
+            sprite_info,
+            masks,
+            animations: cart.animations,
+            map: cart.map,
+            fonts: cart.fonts,
+            canvases: Vec::new(),
+            target: None,
+            recorder: None,
+            cursor: Cursor::Os,
+            cursor_layer: CursorLayer::default(),
             mouse: MousePress::default(),
             keyboard: Keyboard::default(),
+            controls: Controls::load(),
+        }
+    }
+
+    //Every lua draw should go through this so camera, clip and the target get applied
+    pub fn screen(&mut self) -> Screen<'_> {
+        let target = draw_target(&mut self.pixels, &mut self.canvases, self.target);
+        Screen::new(target, &self.draw_state)
+    }
+
+    fn lut(&self) -> PaletteLut {
+        palette_lut(&self.palette, &self.display_pal)
+    }
+
+    //Always the screen, even while drawing into a canvas
+    pub fn screenshot(&self, scale: usize) -> Result<PathBuf, Box<dyn Error>> {
+        save_png(&self.pixels, &self.lut(), scale)
+    }
+
+    pub fn start_recording(&mut self, scale: usize) -> Result<(), Box<dyn Error>> {
+        if self.recorder.is_some() {
+            return Err("Already recording a gif".into());
+        }
+        self.recorder = Some(GifRecorder::start(&self.pixels, &self.lut(), scale)?);
+        Ok(())
+    }
+
+    //None if nothing was recording
+    pub fn stop_recording(&mut self) -> Option<Result<PathBuf, Box<dyn Error>>> {
+        self.recorder.take().map(GifRecorder::finish)
+    }
+
+    //Goes on top of the screen after update so it ends up in screenshots and gifs too
+    pub fn draw_cursor(&mut self) {
+        let (x, y) = (self.mouse.x, self.mouse.y);
+        self.cursor_layer.draw(&mut self.pixels, self.cursor, &self.sprites, x, y);
+    }
+
+    //Has to happen before update so the game never draws over or reads the cursor
+    pub fn erase_cursor(&mut self) {
+        self.cursor_layer.erase(&mut self.pixels);
+    }
+
+    //Called once per game update, a failed write stops the recording instead of every frame erroring
+    pub fn record_frame(&mut self, dt: u128) {
+        let lut = self.lut();
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        if let Err(err) = recorder.capture(&self.pixels, &lut, dt) {
+            self.recorder = None;
+            self.add_log(LogTypes::Err(format!("Gif recording stopped: {err}")));
+        }
+    }
+
+    //F6 saves a screenshot and F8 starts or stops a gif, needs to run before bind_keyboard
+    pub fn capture_hotkey(&mut self, state: ElementState, keycode: VirtualKeyCode) {
+        if state != ElementState::Pressed || self.keyboard.keys_pressed.contains(&keycode) {
+            return;
+        }
+
+        let res = match keycode {
+            VirtualKeyCode::F6 => {
+                self.screenshot(SCALE).map(|path| format!("Saved {}", path.display()))
+            }
+            VirtualKeyCode::F8 => match self.stop_recording() {
+                Some(res) => res.map(|path| format!("Saved {}", path.display())),
+                None => {
+                    self.start_recording(SCALE).map(|_| "Recording gif, F8 to stop".to_string())
+                }
+            },
+            _ => return,
+        };
+        match res {
+            Ok(msg) => self.add_log(LogTypes::Ok(msg)),
+            Err(err) => self.add_log(LogTypes::Err(err.to_string())),
         }
     }
 
@@ -67,8 +197,7 @@ impl LuaAPI {
         let msg = log.to_string();
 
         //Useful for wrapping lines I dont wanna implement scrolling in logs :/
-        for chunk in msg.as_bytes().chunks(30) {
-            let chunk_string = String::from_utf8(chunk.to_vec()).unwrap();
+        for chunk_string in wrap_text(&msg, Font::Mid, 1, LOG_WIDTH) {
             let part: LogTypes = match log {
                 LogTypes::Ok(_) => LogTypes::Ok(chunk_string),
                 LogTypes::Err(_) => LogTypes::Err(chunk_string),
@@ -78,19 +207,216 @@ impl LuaAPI {
     }
 }
 
+//Split out of screen() so draws that read sprites or fonts can borrow them alongside
+fn draw_target<'a>(
+    pixels: &'a mut Framebuffer,
+    canvases: &'a mut [Option<PixelsType>],
+    target: Option<usize>,
+) -> &'a mut dyn Target {
+    match target.and_then(|idx| canvases.get_mut(idx)).and_then(|canvas| canvas.as_mut()) {
+        Some(canvas) => canvas,
+        None => pixels,
+    }
+}
+
+fn canvas_exists(canvases: &[Option<PixelsType>], idx: usize) -> bool {
+    canvases.get(idx).is_some_and(|canvas| canvas.is_some())
+}
+
+fn sprite_info(info: &[SpriteInfo], idx: usize) -> LuaResult<SpriteInfo> {
+    info.get(idx).copied().ok_or(mlua::Error::RuntimeError("Index too large".to_string()))
+}
+
+fn keys_from_name(key: &str) -> LuaResult<&'static [VirtualKeyCode]> {
+    key_from_str(key)
+        .ok_or_else(|| mlua::Error::RuntimeError(format!("{} is not a valid key", key)))
+}
+
+//Buttons go by name or by their 0-6 index, players start at 0
+fn button_keys(
+    controls: &Controls,
+    button: Either<usize, String>,
+    player: Option<usize>,
+) -> LuaResult<&[VirtualKeyCode]> {
+    let button = match button {
+        Either::Left(idx) => Button::from_index(idx),
+        Either::Right(name) => name.parse().ok(),
+    }
+    .ok_or(mlua::Error::RuntimeError("Not a valid button".to_string()))?;
+    let player = player.unwrap_or(0);
+    if player >= PLAYERS {
+        return Err(mlua::Error::RuntimeError(format!(
+            "Player has to be between 0 and {}",
+            PLAYERS - 1
+        )));
+    }
+    Ok(controls.keys(player, button))
+}
+
+fn sprite_mask(masks: &[SpriteMask], idx: usize) -> LuaResult<&SpriteMask> {
+    masks.get(idx).ok_or(mlua::Error::RuntimeError("Index too large".to_string()))
+}
+
+fn animation<'a>(animations: &'a [Animation], name: &str) -> LuaResult<&'a Animation> {
+    animations
+        .iter()
+        .find(|anim| anim.name == name)
+        .ok_or_else(|| mlua::Error::RuntimeError(format!("{} is not a valid animation", name)))
+}
+
+fn check_flag(flag: usize) -> LuaResult<usize> {
+    if flag >= SPRITE_FLAGS {
+        return Err(mlua::Error::RuntimeError(format!(
+            "Flag has to be between 0 and {}",
+            SPRITE_FLAGS - 1
+        )));
+    }
+    Ok(flag)
+}
+
+//Keeps someone from allocating a gigabyte by accident
+const MAX_CANVAS_SIZE: usize = 1024;
+//Same deal for making a new canvas every frame and never freeing it
+const MAX_CANVASES: usize = 64;
+
+//30 characters of the console font
+const LOG_WIDTH: i32 = 120;
+
+//sx, sy, sw, sh, dx, dy, then optional dw, dh
+type SsprArgs = (i32, i32, i32, i32, i32, i32, Option<i32>, Option<i32>);
+
+//x, y, w, h, color, text, then the same options as print_ex
+type PrintBoxArgs = (i32, i32, i32, i32, Colors, String, Option<mlua::Table>);
+
+//Clip name or player, x, y, then t for names or straight to the draw_ex options for players
+type AnimDrawArgs = (
+    Either<String, mlua::UserDataRef<AnimPlayer>>,
+    i32,
+    i32,
+    Option<Either<f64, mlua::Table>>,
+    Option<mlua::Table>,
+);
+
+//Sprite a at xa, ya and sprite b at xb, yb
+type CollideArgs = (usize, i32, i32, usize, i32, i32);
+
+//Sprite at x, y then the rect x, y, w, h
+type CollideRectArgs = (usize, i32, i32, i32, i32, i32, i32);
+
+//Two rects, x, y, w, h each
+type OverlapArgs = (f64, f64, f64, f64, f64, f64, f64, f64);
+
+//cx, cy, sx, sy, cw, ch, layer, all optional
+type MapArgs =
+    (Option<i32>, Option<i32>, Option<i32>, Option<i32>, Option<i32>, Option<i32>, Option<usize>);
+
 #[derive(Clone)]
 pub struct LuaAPIHandle(pub Rc<RefCell<LuaAPI>>);
 
-//Just helper to auto return error if they give a bad color to any draw utils
-//Used like everywhere modify carefully
-fn col_from_str(col: String) -> LuaResult<Colors> {
-    match col.parse::<Colors>() {
-        Ok(c) => Ok(c),
-        Err(_) => Err(mlua::Error::RuntimeError(format!("{} is not a valid color", col))),
+/* Every color argument takes either a name or a 0-16 index (rico.RED and friends)
+ * Indices skip the string work entirely so tight per pixel loops stay cheap
+ * Used like everywhere modify carefully
+ */
+impl FromLua for Colors {
+    fn from_lua(value: Value, _: &Lua) -> LuaResult<Self> {
+        let col = match &value {
+            Value::Integer(idx) => usize::try_from(*idx).ok().and_then(Colors::from_index),
+            Value::Number(n) if n.fract() == 0.0 && *n >= 0.0 => Colors::from_index(*n as usize),
+            Value::String(s) => s.to_str().ok().and_then(|s| s.parse().ok()),
+            _ => None,
+        };
+
+        col.ok_or_else(|| {
+            let shown = value.to_string().unwrap_or_else(|_| value.type_name().to_string());
+            mlua::Error::RuntimeError(format!("{} is not a valid color", shown))
+        })
     }
 }
 
+//Everything in the table is optional, scale sets both axes and scale_x/scale_y override it
+fn transform_opts(opts: Option<mlua::Table>) -> LuaResult<Transform> {
+    let mut tf = Transform::default();
+    if let Some(opts) = opts {
+        let scale: f32 = opts.get::<Option<f32>>("scale")?.unwrap_or(1.0);
+        tf.flip_x = opts.get::<Option<bool>>("flip_x")?.unwrap_or(false);
+        tf.flip_y = opts.get::<Option<bool>>("flip_y")?.unwrap_or(false);
+        tf.angle = opts.get::<Option<f32>>("angle")?.unwrap_or(0.0);
+        tf.scale_x = opts.get::<Option<f32>>("scale_x")?.unwrap_or(scale);
+        tf.scale_y = opts.get::<Option<f32>>("scale_y")?.unwrap_or(scale);
+    }
+    if !(tf.angle.is_finite() && tf.scale_x.is_finite() && tf.scale_y.is_finite()) {
+        return Err(mlua::Error::RuntimeError(
+            "Angle and scale have to be finite numbers".to_string(),
+        ));
+    }
+    Ok(tf)
+}
+
+//Built in font names win, anything else has to be one of the cartridge fonts
+fn font_from_str(fonts: &[BitmapFont], font: Option<String>) -> LuaResult<Font<'_>> {
+    let Some(font) = font else {
+        return Ok(Font::default());
+    };
+
+    if let Ok(builtin) = font.parse::<Font>() {
+        return Ok(builtin);
+    }
+    match fonts.iter().find(|f| f.name == font) {
+        Some(custom) => Ok(Font::Custom(custom)),
+        None => Err(mlua::Error::RuntimeError(format!("{} is not a valid font", font))),
+    }
+}
+
+//Text scale from lua, defaults to 1, anything past the limit would just be a solid block
+fn text_scale(scale: Option<i32>) -> LuaResult<i32> {
+    let scale = scale.unwrap_or(1);
+    if !(1..=MAX_TEXT_SCALE).contains(&scale) {
+        return Err(mlua::Error::RuntimeError(format!(
+            "Text scale has to be between 1 and {MAX_TEXT_SCALE}"
+        )));
+    }
+    Ok(scale)
+}
+
+//font, scale and align out of the options table print_ex and print_box take
+fn text_opts(fonts: &[BitmapFont], opts: Option<mlua::Table>) -> LuaResult<TextStyle<'_>> {
+    let Some(opts) = opts else {
+        return Ok(TextStyle::default());
+    };
+
+    let font = font_from_str(fonts, opts.get::<Option<String>>("font")?)?;
+    let scale = text_scale(opts.get::<Option<i32>>("scale")?)?;
+    let align = match opts.get::<Option<String>>("align")? {
+        Some(align) => align.parse::<Align>().map_err(|_| {
+            mlua::Error::RuntimeError(format!("{} is not a valid alignment", align))
+        })?,
+        None => Align::default(),
+    };
+    Ok(TextStyle { font, scale, align })
+}
+
+//Shared by pal and display_pal
+fn remap(pal: &mut ColorMap, from: Option<Colors>, to: Option<Colors>) -> LuaResult<()> {
+    match (from, to) {
+        (Some(from), Some(to)) => pal[from as usize] = to,
+        (None, None) => *pal = ALL_COLORS,
+        _ => {
+            return Err(mlua::Error::RuntimeError(
+                "Expected either from, to or nothing".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
 impl UserData for LuaAPIHandle {
+    //rico.RED etc, just the index so passing them around costs nothing
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        for col in ALL_COLORS {
+            fields.add_field(col.name(), col as usize);
+        }
+    }
+
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("log", move |_, this, msg: String| {
             let msg = format!("[Log] {}", msg);
@@ -98,93 +424,550 @@ impl UserData for LuaAPIHandle {
             Ok(())
         });
 
-        methods.add_method("set_pix", move |_, this, (x, y, col): (i32, i32, String)| {
-            let val = col_from_str(col)?;
-            set_pix(&mut this.0.borrow_mut().pixels, y, x, val);
+        methods.add_method("set_pix", move |_, this, (x, y, c): (i32, i32, Colors)| {
+            set_pix(&mut this.0.borrow_mut().screen(), y, x, c);
 
             Ok(())
         });
 
-        methods.add_method("get_pix", |_, this, (x, y): (usize, usize)| {
-            if x >= SCREEN_SIZE || y >= SCREEN_SIZE {
-                return Err(mlua::Error::RuntimeError(format!(
-                    "Pixel out of bounds: {}, {}",
-                    x, y
-                )));
+        //Pass true to get the index back instead of the name
+        methods.add_method("get_pix", |_, this, (x, y, as_index): (i32, i32, Option<bool>)| {
+            match this.0.borrow_mut().screen().get_pix(y, x) {
+                Some(col) if as_index == Some(true) => Ok(Either::Right(col as usize)),
+                Some(col) => Ok(Either::Left(col.name())),
+                None => {
+                    Err(mlua::Error::RuntimeError(format!("Pixel out of bounds: {}, {}", x, y)))
+                }
             }
-            Ok(this.0.borrow().pixels[y][x].to_string())
         });
 
         methods.add_method_mut(
             "print_scr",
-            |_, this, (x, y, col, msg): (i32, i32, String, String)| {
-                let c = col_from_str(col)?;
-                print_scr(&mut this.0.borrow_mut().pixels, x, y, c, msg);
+            |_, this, (x, y, c, msg, font): (i32, i32, Colors, String, Option<String>)| {
+                let eng = &mut *this.0.borrow_mut();
+                let style =
+                    TextStyle { font: font_from_str(&eng.fonts, font)?, ..Default::default() };
+                let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+                let mut screen = Screen::new(target, &eng.draw_state);
+                print_text(&mut screen, x, y, c, &msg, &style);
                 Ok(())
             },
         );
 
         methods.add_method_mut(
             "print_scr_mini",
-            |_, this, (x, y, col, msg): (i32, i32, String, String)| {
-                let c = col_from_str(col)?;
-                print_scr_mini(&mut this.0.borrow_mut().pixels, x, y, c, msg);
+            |_, this, (x, y, c, msg): (i32, i32, Colors, String)| {
+                print_scr_mini(&mut this.0.borrow_mut().screen(), x, y, c, msg);
                 Ok(())
             },
         );
 
         methods.add_method_mut(
             "print_scr_mid",
-            |_, this, (x, y, col, msg): (i32, i32, String, String)| {
-                let c = col_from_str(col)?;
-                print_scr_mid(&mut this.0.borrow_mut().pixels, x, y, c, msg);
+            |_, this, (x, y, c, msg): (i32, i32, Colors, String)| {
+                print_scr_mid(&mut this.0.borrow_mut().screen(), x, y, c, msg);
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "print_ex",
+            |_, this, (x, y, c, msg, opts): (i32, i32, Colors, String, Option<mlua::Table>)| {
+                let eng = &mut *this.0.borrow_mut();
+                let style = text_opts(&eng.fonts, opts)?;
+                let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+                let mut screen = Screen::new(target, &eng.draw_state);
+                print_text(&mut screen, x, y, c, &msg, &style);
                 Ok(())
             },
         );
 
+        methods.add_method_mut("print_box", |_, this, (x, y, w, h, c, msg, opts): PrintBoxArgs| {
+            let eng = &mut *this.0.borrow_mut();
+            let style = text_opts(&eng.fonts, opts)?;
+            let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+            let mut screen = Screen::new(target, &eng.draw_state);
+            print_box(&mut screen, (x, y, w, h), c, &msg, &style);
+            Ok(())
+        });
+
+        //Width and height in pixels, multiline text gets the widest line
+        methods.add_method(
+            "text_width",
+            |_, this, (msg, font, scale): (String, Option<String>, Option<i32>)| {
+                let eng = this.0.borrow();
+                let font = font_from_str(&eng.fonts, font)?;
+                let scale = text_scale(scale)?;
+                Ok((text_width(&msg, font, scale), text_height(&msg, font, scale)))
+            },
+        );
+
         methods.add_method_mut("draw", |_, this, (x, y, idx): (i32, i32, usize)| {
-            let mut eng = this.0.borrow_mut();
+            let eng = &mut *this.0.borrow_mut();
 
             if idx >= eng.sprites.len() {
                 return Err(mlua::Error::RuntimeError("Index too large".to_string()));
             }
 
-            let img = eng.sprites[idx].clone();
-            let pixels = &mut eng.pixels;
-            draw(pixels, x, y, &img);
+            let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+            let mut screen = Screen::new(target, &eng.draw_state);
+            draw(&mut screen, x, y, &eng.sprites[idx]);
+            Ok(())
+        });
+
+        //Same options as transform_opts
+        methods.add_method_mut(
+            "draw_ex",
+            |_, this, (x, y, idx, opts): (i32, i32, usize, Option<mlua::Table>)| {
+                let eng = &mut *this.0.borrow_mut();
+
+                if idx >= eng.sprites.len() {
+                    return Err(mlua::Error::RuntimeError("Index too large".to_string()));
+                }
+
+                let tf = transform_opts(opts)?;
+
+                let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+                let mut screen = Screen::new(target, &eng.draw_state);
+                draw_ex(&mut screen, x, y, &eng.sprites[idx], &tf);
+                Ok(())
+            },
+        );
+
+        //Every player gets its own copy of the clip and its own clock
+        methods.add_method("anim", |_, this, name: String| {
+            Ok(AnimPlayer::new(animation(&this.0.borrow().animations, &name)?.clone()))
+        });
+
+        //anim_draw(name, x, y, t, opts) or anim_draw(player, x, y, opts), t is in ms
+        methods.add_method_mut("anim_draw", |_, this, (anim, x, y, t, opts): AnimDrawArgs| {
+            let eng = &mut *this.0.borrow_mut();
+
+            let sprite = match &anim {
+                Either::Left(name) => {
+                    let t = match t {
+                        Some(Either::Left(t)) => t.max(0.0) as u64,
+                        _ => 0,
+                    };
+                    animation(&eng.animations, name)?.sprite_at(t)
+                }
+                Either::Right(player) => player.sprite(),
+            };
+            let opts = match t {
+                Some(Either::Right(opts)) => Some(opts),
+                _ => opts,
+            };
+            //Clips without frames just don't show up
+            let Some(idx) = sprite else {
+                return Ok(());
+            };
+            if idx >= eng.sprites.len() {
+                return Err(mlua::Error::RuntimeError("Index too large".to_string()));
+            }
+
+            let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+            let mut screen = Screen::new(target, &eng.draw_state);
+            match opts {
+                Some(opts) => {
+                    draw_ex(&mut screen, x, y, &eng.sprites[idx], &transform_opts(Some(opts))?)
+                }
+                None => draw(&mut screen, x, y, &eng.sprites[idx]),
+            }
+            Ok(())
+        });
+
+        //Destination size defaults to the source size, so no stretching
+        methods.add_method_mut("sspr", |_, this, (sx, sy, sw, sh, dx, dy, dw, dh): SsprArgs| {
+            let eng = &mut *this.0.borrow_mut();
+            let dst = (dx, dy, dw.unwrap_or(sw), dh.unwrap_or(sh));
+
+            let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+            let mut screen = Screen::new(target, &eng.draw_state);
+            sspr(&mut screen, &eng.sprites, (sx, sy, sw, sh), dst);
+            Ok(())
+        });
+
+        //Everything is optional, defaults to drawing the whole first layer at 0, 0
+        methods.add_method_mut("map", |_, this, args: MapArgs| {
+            let (cx, cy, sx, sy, cw, ch, layer) = args;
+            let eng = &mut *this.0.borrow_mut();
+            let cells = (
+                cx.unwrap_or(0),
+                cy.unwrap_or(0),
+                cw.unwrap_or(eng.map.width as i32),
+                ch.unwrap_or(eng.map.height as i32),
+            );
+            let pos = (sx.unwrap_or(0), sy.unwrap_or(0));
+
+            let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+            let mut screen = Screen::new(target, &eng.draw_state);
+            map(&mut screen, &eng.sprites, &eng.map, layer.unwrap_or(0), cells, pos);
+            Ok(())
+        });
+
+        //nil for empty cells and anything off the map
+        methods.add_method("mget", |_, this, (x, y, layer): (i32, i32, Option<usize>)| {
+            Ok(this.0.borrow().map.get(layer.unwrap_or(0), x, y))
+        });
+
+        methods.add_method_mut(
+            "mset",
+            |_, this, (x, y, idx, layer): (i32, i32, Option<usize>, Option<usize>)| {
+                let eng = &mut *this.0.borrow_mut();
+                if idx.is_some_and(|idx| idx >= eng.sprites.len()) {
+                    return Err(mlua::Error::RuntimeError("Index too large".to_string()));
+                }
+                if !eng.map.set(layer.unwrap_or(0), x, y, idx) {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "Map cell out of bounds: {}, {}",
+                        x, y
+                    )));
+                }
+                Ok(())
+            },
+        );
+
+        //With a flag gives back just that flag, without gives back all of them as a number
+        methods.add_method("fget", |_, this, (idx, flag): (usize, Option<usize>)| {
+            let eng = this.0.borrow();
+            let info = sprite_info(&eng.sprite_info, idx)?;
+            match flag {
+                Some(flag) => Ok(Either::Left(info.flag(check_flag(flag)?))),
+                None => Ok(Either::Right(info.flags)),
+            }
+        });
+
+        //fset(idx, flag, on) sets one flag, fset(idx, bits) sets all of them
+        //Only lasts until the game restarts, the sprite editor is where they get saved
+        methods.add_method_mut("fset", |_, this, (idx, flag, on): (usize, usize, Option<bool>)| {
+            let eng = &mut *this.0.borrow_mut();
+            sprite_info(&eng.sprite_info, idx)?;
+            let info = &mut eng.sprite_info[idx];
+            match on {
+                Some(on) => info.set_flag(check_flag(flag)?, on),
+                None => {
+                    info.flags = u8::try_from(flag).map_err(|_| {
+                        mlua::Error::RuntimeError("Flags have to be between 0 and 255".to_string())
+                    })?
+                }
+            }
             Ok(())
         });
 
+        methods.add_method("sprite_size", |_, this, idx: usize| {
+            match this.0.borrow().sprites.get(idx) {
+                Some(sprite) => Ok((sprite[0].len(), sprite.len())),
+                None => Err(mlua::Error::RuntimeError("Index too large".to_string())),
+            }
+        });
+
+        methods.add_method("sprite_info", |_, this, idx: usize| {
+            sprite_info(&this.0.borrow().sprite_info, idx)
+        });
+
+        //Pixel perfect, Blank pixels never collide. Positions are top lefts like draw
+        methods.add_method("collide", |_, this, (a, xa, ya, b, xb, yb): CollideArgs| {
+            let eng = this.0.borrow();
+            let (a, b) = (sprite_mask(&eng.masks, a)?, sprite_mask(&eng.masks, b)?);
+            Ok(a.overlaps((xa, ya), b, (xb, yb)))
+        });
+
+        methods.add_method(
+            "collide_rect",
+            |_, this, (idx, x, y, rx, ry, rw, rh): CollideRectArgs| {
+                Ok(sprite_mask(&this.0.borrow().masks, idx)?
+                    .overlaps_rect((x, y), (rx, ry, rw, rh)))
+            },
+        );
+
+        //Boxes only, the hitbox from the sprite editor or the opaque bounds if there isn't one
+        methods.add_method("collide_box", |_, this, (a, xa, ya, b, xb, yb): CollideArgs| {
+            let eng = this.0.borrow();
+            let sprite_box = |idx: usize, x: i32, y: i32| -> LuaResult<_> {
+                let info = sprite_info(&eng.sprite_info, idx)?;
+                let hitbox = info.hitbox.or(sprite_mask(&eng.masks, idx)?.bounds());
+                Ok(hitbox.map(|(hx, hy, w, h)| (x + hx, y + hy, w, h)))
+            };
+            match (sprite_box(a, xa, ya)?, sprite_box(b, xb, yb)?) {
+                (Some(a), Some(b)) => Ok(intersect(a, b).is_some()),
+                _ => Ok(false),
+            }
+        });
+
+        //Plain rect vs rect, takes fractions so positions don't need flooring first
+        methods.add_method("overlap", |_, _, (ax, ay, aw, ah, bx, by, bw, bh): OverlapArgs| {
+            Ok(ax < bx + bw && bx < ax + aw && ay < by + bh && by < ay + ah)
+        });
+
+        //Smallest box around the opaque pixels, nothing for an empty sprite
+        methods.add_method("sprite_bounds", |lua, this, idx: usize| {
+            match sprite_mask(&this.0.borrow().masks, idx)?.bounds() {
+                Some(bounds) => bounds.into_lua_multi(lua),
+                None => Ok(mlua::MultiValue::new()),
+            }
+        });
+
+        methods.add_method("map_size", |_, this, ()| {
+            let map = &this.0.borrow().map;
+            Ok((map.width, map.height, map.cell_size, map.layers.len()))
+        });
+
         methods.add_method_mut(
             "rectfill",
-            |_, this, (x, y, w, h, col): (i32, i32, i32, i32, String)| {
-                let c = col_from_str(col)?;
-                rect_fill(&mut this.0.borrow_mut().pixels, x, y, w, h, c);
+            |_, this, (x, y, w, h, c): (i32, i32, i32, i32, Colors)| {
+                rect_fill(&mut this.0.borrow_mut().screen(), x, y, w, h, c);
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut("rect", |_, this, (x, y, w, h, c): (i32, i32, i32, i32, Colors)| {
+            rect(&mut this.0.borrow_mut().screen(), x, y, w, h, c);
+            Ok(())
+        });
+
+        methods.add_method_mut("circle", |_, this, (x, y, r, c): (i32, i32, i32, Colors)| {
+            circle(&mut this.0.borrow_mut().screen(), x, y, r, c);
+            Ok(())
+        });
+
+        methods.add_method_mut(
+            "circle_outline",
+            |_, this, (x, y, r, c): (i32, i32, i32, Colors)| {
+                circle_outline(&mut this.0.borrow_mut().screen(), x, y, r, c);
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "ellipse",
+            |_, this, (x, y, rx, ry, c): (i32, i32, i32, i32, Colors)| {
+                ellipse(&mut this.0.borrow_mut().screen(), x, y, rx, ry, c);
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "ellipsefill",
+            |_, this, (x, y, rx, ry, c): (i32, i32, i32, i32, Colors)| {
+                ellipse_fill(&mut this.0.borrow_mut().screen(), x, y, rx, ry, c);
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "line",
+            |_, this, (x0, y0, x1, y1, c): (i32, i32, i32, i32, Colors)| {
+                line(&mut this.0.borrow_mut().screen(), x0, y0, x1, y1, c);
                 Ok(())
             },
         );
 
         methods.add_method_mut(
-            "rect",
-            |_, this, (x, y, w, h, col): (i32, i32, i32, i32, String)| {
-                let c = col_from_str(col)?;
-                rect(&mut this.0.borrow_mut().pixels, x, y, w, h, c);
+            "tri",
+            |_, this, (x0, y0, x1, y1, x2, y2, c): (i32, i32, i32, i32, i32, i32, Colors)| {
+                tri(&mut this.0.borrow_mut().screen(), [(x0, y0), (x1, y1), (x2, y2)], c);
                 Ok(())
             },
         );
 
-        methods.add_method_mut("circle", |_, this, (x, y, r, col): (i32, i32, i32, String)| {
-            let c = col_from_str(col)?;
-            circle(&mut this.0.borrow_mut().pixels, x, y, r, c);
+        methods.add_method_mut(
+            "trifill",
+            |_, this, (x0, y0, x1, y1, x2, y2, c): (i32, i32, i32, i32, i32, i32, Colors)| {
+                tri_fill(&mut this.0.borrow_mut().screen(), [(x0, y0), (x1, y1), (x2, y2)], c);
+                Ok(())
+            },
+        );
+
+        //Takes a flat list of coords like {x0, y0, x1, y1, ...} since thats easiest to build in lua
+        methods.add_method_mut("poly", |_, this, (coords, c): (Vec<i32>, Colors)| {
+            if coords.len() % 2 != 0 {
+                return Err(mlua::Error::RuntimeError(
+                    "Polygon needs an even number of coordinates".to_string(),
+                ));
+            }
+            let points: Vec<(i32, i32)> = coords.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+            poly(&mut this.0.borrow_mut().screen(), &points, c);
             Ok(())
         });
 
-        methods.add_method_mut("clear", |_, this, col: String| {
-            let c = col_from_str(col)?;
-            clear(&mut this.0.borrow_mut().pixels, c);
+        methods.add_method_mut("fill", |_, this, (x, y, c): (i32, i32, Colors)| {
+            flood_fill(&mut this.0.borrow_mut().screen(), x, y, c);
             Ok(())
         });
 
+        //No color empties the whole target, it's the only way to get a canvas see through again
+        methods.add_method_mut("clear", |_, this, col: Option<Colors>| {
+            let eng = &mut *this.0.borrow_mut();
+            match col {
+                Some(col) => clear(&mut eng.screen(), col),
+                None => {
+                    let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+                    let (bx, by, bw, bh) = target.bounds();
+                    for y in by..by + bh {
+                        for x in bx..bx + bw {
+                            target.put_pix(y, x, Colors::Blank);
+                        }
+                    }
+                }
+            }
+            Ok(())
+        });
+
+        //Starts out fully see through, the handle is just an index like sprites
+        methods.add_method_mut("canvas_new", |_, this, (w, h): (usize, usize)| {
+            if w == 0 || h == 0 || w > MAX_CANVAS_SIZE || h > MAX_CANVAS_SIZE {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "Canvas size has to be between 1 and {}",
+                    MAX_CANVAS_SIZE
+                )));
+            }
+
+            let eng = &mut *this.0.borrow_mut();
+            let canvas = Some(vec![vec![Colors::Blank; w]; h]);
+            if let Some(idx) = eng.canvases.iter().position(|c| c.is_none()) {
+                eng.canvases[idx] = canvas;
+                return Ok(idx);
+            }
+            if eng.canvases.len() >= MAX_CANVASES {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "Can't have more than {} canvases, free some with canvas_free",
+                    MAX_CANVASES
+                )));
+            }
+            eng.canvases.push(canvas);
+            Ok(eng.canvases.len() - 1)
+        });
+
+        //Its handle can get handed out again, drawing into it goes back to the screen
+        methods.add_method_mut("canvas_free", |_, this, canvas: usize| {
+            let eng = &mut *this.0.borrow_mut();
+            if !canvas_exists(&eng.canvases, canvas) {
+                return Err(mlua::Error::RuntimeError("Canvas does not exist".to_string()));
+            }
+            eng.canvases[canvas] = None;
+            if eng.target == Some(canvas) {
+                eng.target = None;
+            }
+            Ok(())
+        });
+
+        //No args goes back to drawing on the screen
+        methods.add_method_mut("target", |_, this, canvas: Option<usize>| {
+            let eng = &mut *this.0.borrow_mut();
+            if canvas.is_some_and(|idx| !canvas_exists(&eng.canvases, idx)) {
+                return Err(mlua::Error::RuntimeError("Canvas does not exist".to_string()));
+            }
+            eng.target = canvas;
+            Ok(())
+        });
+
+        //Draws a canvas onto the current target like a sprite, same options as draw_ex
+        methods.add_method_mut(
+            "blit",
+            |_, this, (canvas, x, y, opts): (usize, i32, i32, Option<mlua::Table>)| {
+                let eng = &mut *this.0.borrow_mut();
+                if !canvas_exists(&eng.canvases, canvas) {
+                    return Err(mlua::Error::RuntimeError("Canvas does not exist".to_string()));
+                }
+                if eng.target == Some(canvas) {
+                    return Err(mlua::Error::RuntimeError(
+                        "Can't blit a canvas onto itself".to_string(),
+                    ));
+                }
+
+                let tf = transform_opts(opts)?;
+                //Taken out for the draw so the target can still be another canvas
+                let src = eng.canvases[canvas].take();
+                if let Some(src) = &src {
+                    let target = draw_target(&mut eng.pixels, &mut eng.canvases, eng.target);
+                    draw_ex(&mut Screen::new(target, &eng.draw_state), x, y, src, &tf);
+                }
+                eng.canvases[canvas] = src;
+                Ok(())
+            },
+        );
+
+        //No args puts the camera back at 0, 0
+        methods.add_method_mut("camera", |_, this, (x, y): (Option<i32>, Option<i32>)| {
+            this.0.borrow_mut().draw_state.camera = (x.unwrap_or(0), y.unwrap_or(0));
+            Ok(())
+        });
+
+        //Clip is in screen space so it stays put when the camera moves, no args removes it
+        methods.add_method_mut(
+            "clip",
+            |_, this, (x, y, w, h): (Option<i32>, Option<i32>, Option<i32>, Option<i32>)| {
+                let clip = match (x, y, w, h) {
+                    (Some(x), Some(y), Some(w), Some(h)) => Some((x, y, w.max(0), h.max(0))),
+                    (None, None, None, None) => None,
+                    _ => {
+                        return Err(mlua::Error::RuntimeError(
+                            "clip takes either x, y, w, h or nothing".to_string(),
+                        ))
+                    }
+                };
+                this.0.borrow_mut().draw_state.clip = clip;
+                Ok(())
+            },
+        );
+
+        //Remaps colors for every following draw, no args resets the whole palette
+        methods.add_method_mut("pal", |_, this, (from, to): (Option<Colors>, Option<Colors>)| {
+            remap(&mut this.0.borrow_mut().draw_state.pal, from, to)
+        });
+
+        //Same as pal but applied to the whole screen when it's shown, so old draws change too
+        methods.add_method_mut(
+            "display_pal",
+            |_, this, (from, to): (Option<Colors>, Option<Colors>)| {
+                remap(&mut this.0.borrow_mut().display_pal, from, to)
+            },
+        );
+
+        //Which sprite colors get skipped when drawing, no args makes only BLANK transparent
+        methods.add_method_mut(
+            "palt",
+            |_, this, (col, transparent): (Option<Colors>, Option<bool>)| {
+                let draw_state = &mut this.0.borrow_mut().draw_state;
+                match col {
+                    Some(col) => draw_state.transparent[col as usize] = transparent.unwrap_or(true),
+                    None => draw_state.transparent = DrawState::default().transparent,
+                }
+                Ok(())
+            },
+        );
+
+        //Pattern for filled shapes, set bits draw alt or nothing without one, no args turns it off
+        methods.add_method_mut("fillp", |_, this, (mask, alt): (Option<u16>, Option<Colors>)| {
+            let draw_state = &mut this.0.borrow_mut().draw_state;
+            draw_state.fill_pattern = mask.unwrap_or(0);
+            draw_state.fill_alt = alt.unwrap_or(Colors::Blank);
+            Ok(())
+        });
+
+        //Scale defaults to the window scale, gives back where the file went
+        methods.add_method("screenshot", |_, this, scale: Option<usize>| {
+            match this.0.borrow().screenshot(scale.unwrap_or(SCALE)) {
+                Ok(path) => Ok(path.display().to_string()),
+                Err(err) => Err(mlua::Error::RuntimeError(err.to_string())),
+            }
+        });
+
+        methods.add_method_mut("gif_start", |_, this, scale: Option<usize>| {
+            this.0
+                .borrow_mut()
+                .start_recording(scale.unwrap_or(SCALE))
+                .map_err(|err| mlua::Error::RuntimeError(err.to_string()))
+        });
+
+        methods.add_method_mut("gif_stop", |_, this, ()| {
+            match this.0.borrow_mut().stop_recording() {
+                Some(Ok(path)) => Ok(path.display().to_string()),
+                Some(Err(err)) => Err(mlua::Error::RuntimeError(err.to_string())),
+                None => Err(mlua::Error::RuntimeError("Not recording a gif".to_string())),
+            }
+        });
+
         methods.add_method_mut("set_frame_rate", |_, this, rate: i32| {
             this.0.borrow_mut().frame_rate = rate;
             Ok(())
@@ -192,27 +975,80 @@ impl UserData for LuaAPIHandle {
 
         methods.add_method("mouse", |_, this, ()| {
             let mut m = this.0.borrow().mouse;
-            if this.0.borrow().mouse.x == -1 {
-                m.pressed = false;
-                m.just_pressed = false;
+            if m.x == -1 {
+                m.release_all();
             }
             Ok(m)
         });
 
+        methods.add_method_mut(
+            "cursor",
+            |lua, this, (cursor, hot_x, hot_y): (Value, Option<i32>, Option<i32>)| {
+                let mut eng = this.0.borrow_mut();
+                eng.cursor = match cursor {
+                    Value::Nil | Value::Boolean(true) => Cursor::Os,
+                    Value::Boolean(false) => Cursor::Hidden,
+                    Value::String(name) => {
+                        let name = name.to_str()?.to_string();
+                        name.parse().map_err(|_| {
+                            mlua::Error::RuntimeError(format!("{} is not a valid cursor", name))
+                        })?
+                    }
+                    idx => {
+                        let idx = usize::from_lua(idx, lua)?;
+                        if idx >= eng.sprites.len() {
+                            return Err(mlua::Error::RuntimeError("Index too large".to_string()));
+                        }
+                        Cursor::Sprite(idx, hot_x.unwrap_or(0), hot_y.unwrap_or(0))
+                    }
+                };
+                Ok(())
+            },
+        );
+
         methods.add_method("key_pressed", |_, this, key: String| {
-            if let Some(kc) = key_from_str(&key) {
-                return Ok(this.0.borrow().keyboard.keys_pressed.contains(&kc));
-            } else {
-                Err(mlua::Error::RuntimeError(format!("{} is not a valid key", key)))
-            }
+            Ok(this.0.borrow().keyboard.pressed(keys_from_name(&key)?))
         });
 
         methods.add_method("key_just_pressed", |_, this, key: String| {
-            if let Some(kc) = key_from_str(&key) {
-                return Ok(this.0.borrow().keyboard.keys_just_pressed.contains(&kc));
-            } else {
-                Err(mlua::Error::RuntimeError(format!("{} is not a valid key", key)))
-            }
+            Ok(this.0.borrow().keyboard.just_pressed(keys_from_name(&key)?))
         });
+
+        methods.add_method("key_just_released", |_, this, key: String| {
+            Ok(this.0.borrow().keyboard.just_released(keys_from_name(&key)?))
+        });
+
+        //For menus and text boxes, delay and interval are in frames
+        methods.add_method(
+            "key_repeat",
+            |_, this, (key, delay, interval): (String, Option<u32>, Option<u32>)| {
+                Ok(this.0.borrow().keyboard.repeated(
+                    keys_from_name(&key)?,
+                    delay.unwrap_or(REPEAT_DELAY),
+                    interval.unwrap_or(REPEAT_INTERVAL),
+                ))
+            },
+        );
+
+        methods.add_method(
+            "btn",
+            |_, this, (button, player): (Either<usize, String>, Option<usize>)| {
+                let eng = this.0.borrow();
+                Ok(eng.keyboard.pressed(button_keys(&eng.controls, button, player)?))
+            },
+        );
+
+        //Repeats while held like key_repeat, good for menus
+        methods.add_method(
+            "btnp",
+            |_, this, (button, player): (Either<usize, String>, Option<usize>)| {
+                let eng = this.0.borrow();
+                let keys = button_keys(&eng.controls, button, player)?;
+                Ok(eng.keyboard.repeated(keys, REPEAT_DELAY, REPEAT_INTERVAL))
+            },
+        );
+
+        //Whatever got typed this frame, empty string if nothing
+        methods.add_method("text_input", |_, this, ()| Ok(this.0.borrow().keyboard.text.clone()));
     }
 }
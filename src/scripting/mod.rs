@@ -1,2 +1,7 @@
+pub mod animation;
 pub mod cartridge;
+pub mod collision;
+pub mod font;
 pub mod lua;
+pub mod sprite_info;
+pub mod tilemap;
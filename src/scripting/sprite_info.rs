@@ -0,0 +1,81 @@
+use bincode::{Decode, Encode};
+use mlua::UserData;
+
+pub const SPRITE_FLAGS: usize = 8;
+
+/* Everything about a sprite that isn't pixels, one per sprite in the sheet
+ * Lives in the cart next to the sheet so it moves with the sprite instead of a lua table
+ * Pivot and hitbox are in sprite pixels, None means the sprite doesn't have one
+ */
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SpriteInfo {
+    //Bit n is flag n, what they mean is up to the game
+    pub flags: u8,
+    pub pivot: Option<(i32, i32)>,
+    //x, y, w, h
+    pub hitbox: Option<(i32, i32, i32, i32)>,
+}
+
+impl SpriteInfo {
+    pub fn flag(&self, flag: usize) -> bool {
+        flag < SPRITE_FLAGS && (self.flags >> flag) & 1 == 1
+    }
+
+    pub fn set_flag(&mut self, flag: usize, on: bool) {
+        if flag >= SPRITE_FLAGS {
+            return;
+        }
+        if on {
+            self.flags |= 1 << flag;
+        } else {
+            self.flags &= !(1 << flag);
+        }
+    }
+
+    //Drops whatever ends up outside a sprite that got resized to size x size
+    pub fn clip(&mut self, size: i32) {
+        if self.pivot.is_some_and(|(x, y)| x >= size || y >= size) {
+            self.pivot = None;
+        }
+        self.hitbox = self.hitbox.and_then(|(x, y, w, h)| {
+            let (w, h) = (w.min(size - x), h.min(size - y));
+            (w > 0 && h > 0).then_some((x, y, w, h))
+        });
+    }
+}
+
+//Carts from before sprite info, or sheets that grew, just get blank info for the rest
+pub fn fit_sprite_info(info: &mut Vec<SpriteInfo>, sprites: usize) {
+    info.resize(sprites, SpriteInfo::default());
+}
+
+//Pivot and hitbox come back as tables so lua can check them against nil
+impl UserData for SpriteInfo {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("flags", |_, this| Ok(this.flags));
+        fields.add_field_method_get("pivot", |lua, this| match this.pivot {
+            Some((x, y)) => {
+                let pivot = lua.create_table()?;
+                pivot.set("x", x)?;
+                pivot.set("y", y)?;
+                Ok(Some(pivot))
+            }
+            None => Ok(None),
+        });
+        fields.add_field_method_get("hitbox", |lua, this| match this.hitbox {
+            Some((x, y, w, h)) => {
+                let hitbox = lua.create_table()?;
+                hitbox.set("x", x)?;
+                hitbox.set("y", y)?;
+                hitbox.set("w", w)?;
+                hitbox.set("h", h)?;
+                Ok(Some(hitbox))
+            }
+            None => Ok(None),
+        });
+    }
+
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("flag", |_, this, flag: usize| Ok(this.flag(flag)));
+    }
+}
@@ -0,0 +1,91 @@
+use bincode::{Decode, Encode};
+
+use crate::engine::sprite::SPRITE_SIZE;
+
+const DEFAULT_MAP_SIZE: usize = 64;
+//1024x1024, way past anything the editor makes but keeps a cart from asking for gigabytes
+pub const MAX_MAP_CELLS: usize = 1 << 20;
+
+/* Grid of sprite indices, None is an empty cell
+ * Layers are all the same size and get drawn separately so lua picks the order
+ * Cells are stored row by row, one flat vec per layer
+ */
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct Tilemap {
+    pub width: usize,
+    pub height: usize,
+    pub cell_size: usize,
+    pub layers: Vec<Vec<Option<usize>>>,
+}
+
+impl Default for Tilemap {
+    fn default() -> Self {
+        Tilemap::new(DEFAULT_MAP_SIZE, DEFAULT_MAP_SIZE, SPRITE_SIZE, 1)
+    }
+}
+
+impl Tilemap {
+    pub fn new(width: usize, height: usize, cell_size: usize, layers: usize) -> Self {
+        Tilemap {
+            width,
+            height,
+            cell_size: cell_size.max(1),
+            layers: vec![vec![None; width * height]; layers.max(1)],
+        }
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    pub fn get(&self, layer: usize, x: i32, y: i32) -> Option<usize> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        self.layers.get(layer)?.get(y as usize * self.width + x as usize).copied().flatten()
+    }
+
+    //Returns false if the cell doesn't exist so lua can error out
+    pub fn set(&mut self, layer: usize, x: i32, y: i32, tile: Option<usize>) -> bool {
+        if !self.contains(x, y) {
+            return false;
+        }
+        let i = y as usize * self.width + x as usize;
+        match self.layers.get_mut(layer).and_then(|cells| cells.get_mut(i)) {
+            Some(cell) => {
+                *cell = tile;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //Same idea as flood_region for pixels but over cells, used by the map editor fill
+    pub fn region(&self, layer: usize, x: i32, y: i32) -> Vec<(i32, i32)> {
+        if layer >= self.layers.len() || !self.contains(x, y) {
+            return Vec::new();
+        }
+
+        let tile = self.get(layer, x, y);
+        let mut region = Vec::new();
+        let mut q = vec![(x, y)];
+        let mut visited = vec![false; self.width * self.height];
+        while let Some((cx, cy)) = q.pop() {
+            let i = cy as usize * self.width + cx as usize;
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            region.push((cx, cy));
+
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (nx, ny) = (cx + dx, cy + dy);
+                if self.contains(nx, ny) && self.get(layer, nx, ny) == tile {
+                    q.push((nx, ny));
+                }
+            }
+        }
+
+        region
+    }
+}